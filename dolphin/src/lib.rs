@@ -9,7 +9,7 @@ mod logger;
 pub use logger::Log;
 
 mod osd;
-pub use osd::{Color, Duration};
+pub use osd::{Color, DolphinOsd, Duration, OsdSink, RecordingOsd};
 
 /// These types are primarily used by the `ffi` crate to wire up Dolphin functionality,
 /// and you shouldn't need to touch them yourself. We're just re-exporting these under
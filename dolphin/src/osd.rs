@@ -2,8 +2,11 @@
 //! functionality in Dolphin.
 
 use std::ffi::c_char;
+use std::fmt::Debug;
 
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock};
+
+use crate::Dolphin;
 
 /// A type that mirrors.
 pub type AddOSDMessageFn = unsafe extern "C" fn(*const c_char, u32, u32);
@@ -22,7 +25,7 @@ pub fn set_global_hook(add_osd_message_fn: AddOSDMessageFn) {
 }
 
 /// Represents colors that the On-Screen-Display could render.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Color {
     Cyan,
     Green,
@@ -45,7 +48,7 @@ impl Color {
 }
 
 /// Represents the length of time an On-Screen-Display message should stay on screen.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Duration {
     Short,
     Normal,
@@ -64,3 +67,44 @@ impl Duration {
         }
     }
 }
+
+/// Something that can render On-Screen-Display messages.
+///
+/// Modules that show messages hold one of these rather than calling `Dolphin::add_osd_message`
+/// directly, which lets tests swap in a `RecordingOsd` and assert on what the user would see.
+pub trait OsdSink: Debug + Send + Sync {
+    /// Renders (or otherwise handles) a message.
+    fn add_message(&self, color: Color, duration: Duration, message: &str);
+}
+
+/// The real sink, which passes messages through to Dolphin.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DolphinOsd;
+
+impl OsdSink for DolphinOsd {
+    fn add_message(&self, color: Color, duration: Duration, message: &str) {
+        Dolphin::add_osd_message(color, duration, message);
+    }
+}
+
+/// A sink that just holds on to every message it receives, for use in tests.
+#[derive(Debug, Default)]
+pub struct RecordingOsd {
+    messages: Mutex<Vec<(Color, Duration, String)>>,
+}
+
+impl RecordingOsd {
+    /// Returns a copy of every message received so far, oldest first.
+    pub fn messages(&self) -> Vec<(Color, Duration, String)> {
+        self.messages.lock().expect("Unable to lock recorded OSD messages").clone()
+    }
+}
+
+impl OsdSink for RecordingOsd {
+    fn add_message(&self, color: Color, duration: Duration, message: &str) {
+        self.messages
+            .lock()
+            .expect("Unable to lock recorded OSD messages")
+            .push((color, duration, message.to_string()));
+    }
+}
@@ -5,28 +5,73 @@
 //! `SlippiEXIDevice` and forwards calls over the C FFI. This has a fairly clean mapping to "when
 //! Slippi stuff is happening" and enables us to let the Rust side live in its own world.
 
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use dolphin_integrations::Log;
-use slippi_game_reporter::GameReporter;
-use slippi_gg_api::APIClient;
-use slippi_jukebox::Jukebox;
+use slippi_game_reporter::{GameReporter, GameReporterConfig, SessionStats};
+use slippi_gg_api::{APIClient, HealthMonitor};
+use slippi_jukebox::{Jukebox, JukeboxError, JukeboxOptions, NowPlaying};
 use slippi_user::UserManager;
 
 mod config;
 pub use config::{Config, FilePathsConfig, SCMConfig};
 
+/// The file, next to `user.json`, that the launcher can drop additional known desync ISO
+/// hashes into without needing a new build.
+const DESYNC_ISO_LIST_FILE: &str = "desync-isos.json";
+
 /// An EXI Device subclass specific to managing and interacting with the game itself.
 #[derive(Debug)]
 pub struct SlippiEXIDevice {
     config: Config,
+    pub api_client: APIClient,
+    pub api_health: HealthMonitor,
     pub game_reporter: GameReporter,
     pub user_manager: UserManager,
     pub jukebox: Option<Jukebox>,
+    jukebox_error: Option<JukeboxError>,
+}
+
+/// A snapshot of this session's activity across subsystems. Anything from a subsystem that
+/// isn't running (e.g, the Jukebox) is left zeroed/empty.
+///
+/// Rank and win streaks aren't tracked on the Rust side yet, so they aren't included here.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionSummary {
+    /// Game reports the server has accepted.
+    pub reports_sent: u32,
+
+    /// Distinct matches that at least one game report was logged for.
+    pub matches_played: u32,
+
+    /// The song the Jukebox is playing, if it's running and not silent.
+    pub now_playing: Option<NowPlaying>,
+}
+
+impl SessionSummary {
+    /// Combines the snapshots read from each subsystem.
+    fn assemble(reporter: SessionStats, now_playing: Option<NowPlaying>) -> Self {
+        Self {
+            reports_sent: reporter.reports_sent,
+            matches_played: reporter.matches_played,
+            now_playing,
+        }
+    }
 }
 
 pub enum JukeboxConfiguration {
     Start {
         initial_dolphin_system_volume: u8,
         initial_dolphin_music_volume: u8,
+
+        /// How long newly started songs take to ramp up to full volume. Zero starts them
+        /// at full volume immediately.
+        fade_in: Duration,
+
+        /// If set, the audio device is released after this long without music, and
+        /// re-acquired on the next song.
+        idle_timeout: Option<Duration>,
     },
     Stop,
 }
@@ -46,7 +91,17 @@ impl SlippiEXIDevice {
             config.scm.slippi_semver.clone(),
         );
 
-        let game_reporter = GameReporter::new(api_client.clone(), user_manager.clone(), config.paths.iso.clone());
+        let reporter_config = GameReporterConfig {
+            desync_iso_list_path: desync_iso_list_path(&config.paths.user_json),
+            ..GameReporterConfig::default()
+        };
+
+        let game_reporter = GameReporter::with_config(
+            api_client.clone(),
+            user_manager.clone(),
+            config.paths.iso.clone(),
+            reporter_config,
+        );
 
         // Playback has no need to deal with this.
         // (We could maybe silo more?)
@@ -55,9 +110,12 @@ impl SlippiEXIDevice {
 
         Self {
             config,
+            api_client,
+            api_health: HealthMonitor::new(),
             game_reporter,
             user_manager,
             jukebox: None,
+            jukebox_error: None,
         }
     }
 
@@ -67,6 +125,11 @@ impl SlippiEXIDevice {
     /// Stubbed for now, but this would get called by the C++ EXI device on DMARead.
     pub fn dma_read(&mut self, _address: usize, _size: usize) {}
 
+    /// Returns a summary of this session's activity, for overlays and end-of-session screens.
+    pub fn session_summary(&self) -> SessionSummary {
+        SessionSummary::assemble(self.game_reporter.session_stats(), self.jukebox_now_playing())
+    }
+
     /// Configures a new Jukebox, or ensures an existing one is dropped if it's being disabled.
     pub fn configure_jukebox(&mut self, config: JukeboxConfiguration) {
         if let JukeboxConfiguration::Stop = config {
@@ -82,23 +145,111 @@ impl SlippiEXIDevice {
         if let JukeboxConfiguration::Start {
             initial_dolphin_system_volume,
             initial_dolphin_music_volume,
+            fade_in,
+            idle_timeout,
         } = config
         {
+            let options = JukeboxOptions {
+                fade_in,
+                idle_timeout,
+                ..JukeboxOptions::default()
+            };
+
             match Jukebox::new(
                 self.config.paths.iso.clone(),
                 initial_dolphin_system_volume,
                 initial_dolphin_music_volume,
+                options,
             ) {
                 Ok(jukebox) => {
                     self.jukebox = Some(jukebox);
+                    self.jukebox_error = None;
                 },
 
-                Err(e) => tracing::error!(
-                    target: Log::SlippiOnline,
-                    error = ?e,
-                    "Failed to start Jukebox"
-                ),
+                Err(e) => {
+                    tracing::error!(
+                        target: Log::SlippiOnline,
+                        error = ?e,
+                        "Failed to start Jukebox"
+                    );
+
+                    self.jukebox_error = Some(e);
+                },
             }
         }
     }
+
+    /// Whether a Jukebox is currently configured and running.
+    pub fn is_jukebox_active(&self) -> bool {
+        self.jukebox.is_some()
+    }
+
+    /// Returns the error from the most recent failed attempt to start the Jukebox (e.g, an
+    /// unsupported ISO). This is cleared once a Jukebox starts successfully.
+    pub fn jukebox_last_error(&self) -> Option<&JukeboxError> {
+        self.jukebox_error.as_ref()
+    }
+
+    /// Returns the song the Jukebox is currently playing, if it's running and not silent.
+    pub fn jukebox_now_playing(&self) -> Option<NowPlaying> {
+        self.jukebox.as_ref().and_then(Jukebox::now_playing)
+    }
+}
+
+/// Returns the launcher-provided desync ISO list next to `user.json`, if there is one. Most
+/// installs won't have one, so a missing file isn't worth a warning from the hasher.
+fn desync_iso_list_path(user_json_path: &str) -> Option<PathBuf> {
+    let path = Path::new(user_json_path).parent()?.join(DESYNC_ISO_LIST_FILE);
+    path.is_file().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_summaries_combine_subsystem_snapshots() {
+        let reporter = SessionStats {
+            reports_sent: 3,
+            matches_played: 2,
+        };
+
+        let song = NowPlaying {
+            hps_offset: 0x1a2b3c,
+            hps_length: 1024,
+        };
+
+        assert_eq!(
+            SessionSummary::assemble(reporter, Some(song)),
+            SessionSummary {
+                reports_sent: 3,
+                matches_played: 2,
+                now_playing: Some(song),
+            }
+        );
+
+        // Nothing running means nothing to report.
+        assert_eq!(
+            SessionSummary::assemble(SessionStats::default(), None),
+            SessionSummary::default()
+        );
+    }
+
+    #[test]
+    fn finds_the_desync_iso_list_next_to_user_json() {
+        let folder = std::env::temp_dir().join(format!("slippi-exi-desync-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+
+        let user_json = folder.join("user.json");
+        let user_json = user_json.to_str().unwrap();
+        assert_eq!(desync_iso_list_path(user_json), None);
+
+        std::fs::write(folder.join(DESYNC_ISO_LIST_FILE), "[]").unwrap();
+        assert_eq!(desync_iso_list_path(user_json), Some(folder.join(DESYNC_ISO_LIST_FILE)));
+
+        // No user folder (e.g, a path that wasn't valid UTF-8) means no list.
+        assert_eq!(desync_iso_list_path(""), None);
+
+        let _ = std::fs::remove_dir_all(&folder);
+    }
 }
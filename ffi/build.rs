@@ -14,8 +14,16 @@ fn main() {
         ..Default::default()
     };
 
+    // Some types aren't referenced by any exported function signature (e.g, because the
+    // function takes the raw value and validates it), but the C++ side still uses them.
+    let export_config = cbindgen::ExportConfig {
+        include: vec!["SlippiMatchmakingOnlinePlayMode".to_string()],
+        ..Default::default()
+    };
+
     let config = cbindgen::Config {
         enumeration: enum_config,
+        export: export_config,
         ..Default::default()
     };
 
@@ -4,10 +4,26 @@
 #include <ostream>
 #include <new>
 
+/// Mirrors `slippi_gg_api::HealthCheckState` (flattened with `HealthCheckStatus`) for
+/// cbindgen, which cannot see the types in the other crate.
+enum SlippiApiHealthStatus {
+  Ok = 0,
+  AuthFailure = 1,
+  NetworkFailure = 2,
+  ServerError = 3,
+  TimedOut = 4,
+  Pending = 5,
+  NotStarted = 6,
+};
+
 /// This enum is duplicated from `slippi_game_reporter::OnlinePlayMode` in order
 /// to appease cbindgen, which cannot see the type from the other module for
 /// inspection.
 ///
+/// The FFI functions accept the raw byte and validate it on the Rust side (a bad value
+/// received as a Rust enum would be undefined behavior), so this is only exported to keep
+/// the named values available to the C++ side.
+///
 /// This enum will likely go away as things move towards Rust, since it's effectively
 /// just C FFI glue code.
 enum SlippiMatchmakingOnlinePlayMode {
@@ -30,6 +46,25 @@ struct SlippiRustEXIConfig {
   void (*osd_add_msg_fn)(const char*, uint32_t, uint32_t);
 };
 
+/// A C-compatible copy of `slippi_gg_api::HealthCheckState`.
+struct SlippiApiHealthCheck {
+  SlippiApiHealthStatus status;
+  /// The round-trip time of the check in milliseconds, or `-1` if it hasn't finished or
+  /// the server never responded.
+  int64_t latency_ms;
+};
+
+/// A C-compatible copy of `slippi_exi_device::SessionSummary`.
+///
+/// This must be passed back to `slprs_exi_device_free_session_stats` to free `now_playing`.
+struct RustSessionStats {
+  uint32_t reports_sent;
+  uint32_t matches_played;
+  /// A description of the song the Jukebox is playing (see `slprs_jukebox_now_playing`), or
+  /// a null pointer if it's silent or not running.
+  const char *now_playing;
+};
+
 /// An intermediary type for moving `UserInfo` across the FFI boundary.
 ///
 /// This type is C compatible, and we coerce Rust types into C types for this struct to
@@ -87,6 +122,8 @@ void slprs_exi_device_dma_read(uintptr_t exi_device_instance_ptr,
 /// will then add it to the processing pipeline.
 ///
 /// The reporter will manage the actual... reporting.
+///
+/// A `0` report pointer (i.e, one that `slprs_game_report_create` refused to create) is ignored.
 void slprs_exi_device_log_game_report(uintptr_t instance_ptr, uintptr_t game_report_instance_ptr);
 
 /// Calls through to `SlippiGameReporter::start_new_session`.
@@ -102,6 +139,47 @@ void slprs_exi_device_report_match_completion(uintptr_t instance_ptr,
 /// match abandon event.
 void slprs_exi_device_report_match_abandonment(uintptr_t instance_ptr, const char *match_id);
 
+/// Calls through to `SlippiGameReporter::set_paused`. While paused, reports are still
+/// queued but aren't sent until reporting is resumed.
+void slprs_exi_device_set_reporter_paused(uintptr_t instance_ptr, bool paused);
+
+/// Returns how far along (0-100) the game reporter's ISO MD5 hash is, so the UI can show
+/// progress rather than appearing to hang on slow disks.
+uint8_t slprs_exi_device_get_iso_hash_progress(uintptr_t instance_ptr);
+
+/// Returns the ISO's MD5 hash once the game reporter has computed it, or a null pointer if
+/// it's still in progress (see `slprs_exi_device_get_iso_hash_progress`). This doesn't block.
+///
+/// A non-null return value _must_ be passed back to `slprs_exi_device_free_iso_hash` to free memory.
+const char *slprs_exi_device_get_iso_hash(uintptr_t instance_ptr);
+
+/// Takes back ownership of a string returned from `slprs_exi_device_get_iso_hash` and drops it.
+void slprs_exi_device_free_iso_hash(const char *ptr);
+
+/// Calls through to `SlippiGameReporter::set_enabled`. While disabled, reports are logged
+/// rather than sent, which keeps local development builds from reporting real matches.
+void slprs_exi_device_set_reporter_enabled(uintptr_t instance_ptr, bool enabled);
+
+/// Starts a health check (a minimal GraphQL query) through the API client on the EXI device.
+/// This returns immediately; the check runs on a background thread, and its result can be
+/// read via `slprs_api_healthcheck_poll`. Starting a check while one is pending is a no-op.
+void slprs_api_healthcheck_start(uintptr_t exi_device_instance_ptr);
+
+/// Returns the state of the most recent health check started via `slprs_api_healthcheck_start`:
+/// `Pending` while it's in flight, or where (if anywhere) connectivity broke once it's done,
+/// along with the round-trip latency. This is returned by value, so there's nothing to free,
+/// and it never blocks, so it's safe to poll from the UI thread.
+SlippiApiHealthCheck slprs_api_healthcheck_poll(uintptr_t exi_device_instance_ptr);
+
+/// Returns a summary of this session's activity from the EXI device at the provided pointer.
+///
+/// The returned struct _must_ be passed back to `slprs_exi_device_free_session_stats` to free memory.
+RustSessionStats slprs_exi_device_get_session_stats(uintptr_t exi_device_instance_ptr);
+
+/// Takes back ownership of the strings in a `RustSessionStats` returned from
+/// `slprs_exi_device_get_session_stats` and drops them.
+void slprs_exi_device_free_session_stats(RustSessionStats stats);
+
 /// Calls through to `SlippiGameReporter::push_replay_data`.
 void slprs_exi_device_reporter_push_replay_data(uintptr_t instance_ptr,
                                                 const uint8_t *data,
@@ -110,10 +188,16 @@ void slprs_exi_device_reporter_push_replay_data(uintptr_t instance_ptr,
 /// Configures the Jukebox process. This needs to be called after the EXI device is created
 /// in order for certain pieces of Dolphin to be properly initalized; this may change down
 /// the road though and is not set in stone.
+///
+/// `fade_in_ms` is how long newly started songs take to ramp up to full volume; `0` starts
+/// them at full volume immediately. `idle_timeout_secs` is how long the Jukebox can go without
+/// playing music before it releases the audio device; `0` (the default) never releases it.
 void slprs_exi_device_configure_jukebox(uintptr_t exi_device_instance_ptr,
                                         bool is_enabled,
                                         uint8_t initial_dolphin_system_volume,
-                                        uint8_t initial_dolphin_music_volume);
+                                        uint8_t initial_dolphin_music_volume,
+                                        uint32_t fade_in_ms,
+                                        uint32_t idle_timeout_secs);
 
 /// Creates a new Player Report and leaks it, returning the pointer.
 ///
@@ -132,9 +216,12 @@ uintptr_t slprs_player_report_create(const char *uid,
 ///
 /// This is expected to ultimately be passed to the game reporter, which will handle
 /// destruction and cleanup.
+///
+/// If `online_mode` is not a known `SlippiMatchmakingOnlinePlayMode` value, this logs and
+/// returns `0` instead. The other game report functions treat a `0` pointer as a no-op.
 uintptr_t slprs_game_report_create(const char *uid,
                                    const char *play_key,
-                                   SlippiMatchmakingOnlinePlayMode online_mode,
+                                   uint8_t online_mode,
                                    const char *match_id,
                                    uint32_t duration_frames,
                                    uint32_t game_index,
@@ -157,6 +244,21 @@ void slprs_jukebox_start_song(uintptr_t exi_device_instance_ptr,
 /// Calls through to `Jukebox::stop_music`.
 void slprs_jukebox_stop_music(uintptr_t exi_device_instance_ptr);
 
+/// Calls through to `Jukebox::pause`.
+void slprs_jukebox_pause(uintptr_t exi_device_instance_ptr);
+
+/// Calls through to `Jukebox::resume`.
+void slprs_jukebox_resume(uintptr_t exi_device_instance_ptr);
+
+/// Calls through to `Jukebox::mute`.
+void slprs_jukebox_mute(uintptr_t exi_device_instance_ptr);
+
+/// Calls through to `Jukebox::unmute`.
+void slprs_jukebox_unmute(uintptr_t exi_device_instance_ptr);
+
+/// Calls through to `Jukebox::reinit_audio`.
+void slprs_jukebox_reinit_audio(uintptr_t exi_device_instance_ptr);
+
 /// Calls through to `Jukebox::set_volume` with the Melee volume control.
 void slprs_jukebox_set_melee_music_volume(uintptr_t exi_device_instance_ptr, uint8_t volume);
 
@@ -166,6 +268,30 @@ void slprs_jukebox_set_dolphin_system_volume(uintptr_t exi_device_instance_ptr,
 /// Calls through to `Jukebox::set_volume` with the DolphinMusic volume control.
 void slprs_jukebox_set_dolphin_music_volume(uintptr_t exi_device_instance_ptr, uint8_t volume);
 
+/// Returns whether the Jukebox on the EXI device is currently running.
+bool slprs_jukebox_is_active(uintptr_t exi_device_instance_ptr);
+
+/// Returns the reason the Jukebox last failed to start (e.g, an unsupported ISO), or a null
+/// pointer if it hasn't failed.
+///
+/// A non-null return value _must_ be passed back to `slprs_jukebox_free_last_error` to free memory.
+const char *slprs_jukebox_last_error(uintptr_t exi_device_instance_ptr);
+
+/// Takes back ownership of a string returned from `slprs_jukebox_last_error` and drops it.
+void slprs_jukebox_free_last_error(const char *ptr);
+
+/// Returns a description of the song the Jukebox is currently playing, or a null pointer if
+/// it's silent (or not running).
+///
+/// There's no track list to resolve stage names from, so this describes the song by where its
+/// hps file lives (e.g, `hps offset: 0x1a2b3c, length: 123456`) rather than by stage name.
+///
+/// A non-null return value _must_ be passed back to `slprs_jukebox_free_now_playing` to free memory.
+const char *slprs_jukebox_now_playing(uintptr_t exi_device_instance_ptr);
+
+/// Takes back ownership of a string returned from `slprs_jukebox_now_playing` and drops it.
+void slprs_jukebox_free_now_playing(const char *ptr);
+
 /// This should be called from the Dolphin LogManager initialization to ensure that
 /// all logging needs on the Rust side are configured appropriately.
 ///
@@ -214,6 +340,18 @@ bool slprs_user_update_app(uintptr_t exi_device_instance_ptr);
 /// already from EXI device instantiation.
 void slprs_user_listen_for_login(uintptr_t exi_device_instance_ptr);
 
+/// Instructs the `UserManager` on the EXI Device at the provided pointer to re-fetch the
+/// current user's info from the server. This runs on a background thread.
+void slprs_user_refresh(uintptr_t exi_device_instance_ptr);
+
+/// Checks whether `user.json` exists but keeps failing to be read (e.g, it's locked by another
+/// process), so the UI can tell the user something is blocking the file.
+bool slprs_user_get_is_user_json_blocked(uintptr_t exi_device_instance_ptr);
+
+/// Checks whether the folder holding `user.json` could be written to, so the UI can warn the
+/// user that anything saved there will only last for this session.
+bool slprs_user_get_is_config_folder_writable(uintptr_t exi_device_instance_ptr);
+
 /// Instructs the `UserManager` on the EXI Device at the provided pointer to sign the user out.
 /// This will delete the `user.json` file from the underlying filesystem.
 void slprs_user_logout(uintptr_t exi_device_instance_ptr);
@@ -233,6 +371,8 @@ bool slprs_user_get_is_logged_in(uintptr_t exi_device_instance_ptr);
 /// This involves slightly more allocations than ideal, so this shouldn't be called in a hot path.
 /// Over time this issue will not matter as once Matchmaking is moved to Rust we can share things
 /// quite easily.
+///
+/// Returns a null pointer if the EXI device pointer is null.
 RustUserInfo *slprs_user_get_info(uintptr_t exi_device_instance_ptr);
 
 /// Takes ownership back of a `UserInfo` struct and drops it.
@@ -245,11 +385,13 @@ void slprs_user_free_info(RustUserInfo *ptr);
 /// Returns a C-compatible struct containing the chat message options for the current user.
 ///
 /// The return value of this _must_ be passed back to `slprs_user_free_messages` to free memory.
+/// A null EXI device pointer returns a null pointer.
 RustChatMessages *slprs_user_get_messages(uintptr_t exi_device_instance_ptr);
 
 /// Returns a C-compatible struct containing the default chat message options.
 ///
 /// The return value of this _must_ be passed back to `slprs_user_free_messages` to free memory.
+/// A null EXI device pointer returns a null pointer.
 RustChatMessages *slprs_user_get_default_messages(uintptr_t exi_device_instance_ptr);
 
 /// Takes back ownership of a `RustChatMessages` instance and frees the underlying data
@@ -1,10 +1,13 @@
-use std::ffi::c_char;
+use std::ffi::{c_char, CString};
+use std::ptr;
+use std::time::Duration;
 
 use dolphin_integrations::Log;
 use slippi_exi_device::{Config, FilePathsConfig, JukeboxConfiguration, SCMConfig, SlippiEXIDevice};
-use slippi_game_reporter::GameReport;
+use slippi_game_reporter::{GameEndMode, GameReport};
+use slippi_gg_api::{HealthCheckState, HealthCheckStatus};
 
-use crate::c_str_to_string;
+use crate::{c_str_to_string, try_c_str_to_string, with, with_returning, with_returning_or};
 
 /// A configuration struct for passing over certain argument types from the C/C++ side.
 ///
@@ -51,8 +54,10 @@ pub extern "C" fn slprs_exi_device_create(config: SlippiRustEXIConfig) -> usize
 
     let exi_device = Box::new(SlippiEXIDevice::new(Config {
         paths: FilePathsConfig {
-            iso: c_str_to_string(config.iso_path, fn_name, "iso_path"),
-            user_json: c_str_to_string(config.user_json_path, fn_name, "user_json"),
+            // Paths may not be valid UTF-8. An empty path just leaves the features that need
+            // it (ISO hashing, Jukebox, login) logging errors instead of taking Dolphin down.
+            iso: try_c_str_to_string(config.iso_path, fn_name, "iso_path").unwrap_or_default(),
+            user_json: try_c_str_to_string(config.user_json_path, fn_name, "user_json").unwrap_or_default(),
         },
 
         scm: SCMConfig {
@@ -75,6 +80,11 @@ pub extern "C" fn slprs_exi_device_create(config: SlippiRustEXIConfig) -> usize
 /// can safely shut down and clean up.
 #[no_mangle]
 pub extern "C" fn slprs_exi_device_destroy(exi_device_instance_ptr: usize) {
+    if exi_device_instance_ptr == 0 {
+        tracing::error!(target: Log::SlippiOnline, "Asked to destroy a null Rust EXI Device, ignoring");
+        return;
+    }
+
     tracing::warn!(
         target: Log::SlippiOnline,
         ptr = exi_device_instance_ptr,
@@ -96,15 +106,9 @@ pub extern "C" fn slprs_exi_device_destroy(exi_device_instance_ptr: usize) {
 /// `virtual void DMAWrite(u32 _uAddr, u32 _uSize);`
 #[no_mangle]
 pub extern "C" fn slprs_exi_device_dma_write(exi_device_instance_ptr: usize, address: *const u8, size: *const u8) {
-    // Coerce the instance back from the pointer. This is theoretically safe since we control
-    // the C++ side and can guarantee that the `exi_device_instance_ptr` pointer is only owned
-    // by the C++ EXI device, and is created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(exi_device_instance_ptr as *mut SlippiEXIDevice) };
-
-    device.dma_write(address as usize, size as usize);
-
-    // Fall back into a raw pointer so Rust doesn't obliterate the object
-    let _leak = Box::into_raw(device);
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        device.dma_write(address as usize, size as usize);
+    });
 }
 
 /// This method should be called from the EXI device subclass shim that's registered on
@@ -113,15 +117,9 @@ pub extern "C" fn slprs_exi_device_dma_write(exi_device_instance_ptr: usize, add
 /// `virtual void DMARead(u32 _uAddr, u32 _uSize);`
 #[no_mangle]
 pub extern "C" fn slprs_exi_device_dma_read(exi_device_instance_ptr: usize, address: *const u8, size: *const u8) {
-    // Coerce the instance from the pointer. This is theoretically safe since we control
-    // the C++ side and can guarantee that the `exi_device_instance_ptr` pointer is only owned
-    // by the C++ EXI device, and is created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(exi_device_instance_ptr as *mut SlippiEXIDevice) };
-
-    device.dma_read(address as usize, size as usize);
-
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        device.dma_read(address as usize, size as usize);
+    });
 }
 
 /// Moves ownership of the `GameReport` at the specified address to the
@@ -129,72 +127,250 @@ pub extern "C" fn slprs_exi_device_dma_read(exi_device_instance_ptr: usize, addr
 /// will then add it to the processing pipeline.
 ///
 /// The reporter will manage the actual... reporting.
+///
+/// A `0` report pointer (i.e, one that `slprs_game_report_create` refused to create) is ignored.
 #[no_mangle]
 pub extern "C" fn slprs_exi_device_log_game_report(instance_ptr: usize, game_report_instance_ptr: usize) {
-    // Coerce the instances from the pointers. This is theoretically safe since we control
-    // the C++ side and can guarantee that the pointers are only owned
-    // by us, and are created/destroyed with the corresponding lifetimes.
-    let (mut device, game_report) = unsafe {
-        (
-            Box::from_raw(instance_ptr as *mut SlippiEXIDevice),
-            Box::from_raw(game_report_instance_ptr as *mut GameReport),
-        )
-    };
+    if game_report_instance_ptr == 0 {
+        tracing::warn!(target: Log::SlippiOnline, "Ignoring null game report");
+        return;
+    }
 
-    device.game_reporter.log_report(*game_report);
+    // Coerce the report from the pointer. This is theoretically safe since we control
+    // the C++ side and can guarantee that the pointer is only owned by us, and ownership
+    // is handed over to the reporter here.
+    let game_report = unsafe { Box::from_raw(game_report_instance_ptr as *mut GameReport) };
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+    with::<SlippiEXIDevice, _>(instance_ptr, move |device| {
+        device.game_reporter.log_report(*game_report);
+    });
 }
 
 /// Calls through to `SlippiGameReporter::start_new_session`.
 #[no_mangle]
 pub extern "C" fn slprs_exi_device_start_new_reporter_session(instance_ptr: usize) {
-    // Coerce the instances from the pointers. This is theoretically safe since we control
-    // the C++ side and can guarantee that the pointers are only owned
-    // by us, and are created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(instance_ptr as *mut SlippiEXIDevice) };
-
-    device.game_reporter.start_new_session();
-
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+    with::<SlippiEXIDevice, _>(instance_ptr, |device| {
+        device.game_reporter.start_new_session();
+    });
 }
 
 /// Calls through to the `SlippiGameReporter` on the EXI device to report a
 /// match completion event.
 #[no_mangle]
 pub extern "C" fn slprs_exi_device_report_match_completion(instance_ptr: usize, match_id: *const c_char, end_mode: u8) {
-    // Coerce the instances from the pointers. This is theoretically safe since we control
-    // the C++ side and can guarantee that the pointers are only owned
-    // by us, and are created/destroyed with the corresponding lifetimes.
-    let device = unsafe { Box::from_raw(instance_ptr as *mut SlippiEXIDevice) };
-
     let fn_name = "slprs_exi_device_report_match_completion";
-    let match_id = c_str_to_string(match_id, fn_name, "match_id");
 
-    device.game_reporter.report_completion(match_id, end_mode);
+    let end_mode = match GameEndMode::try_from(end_mode) {
+        Ok(end_mode) => end_mode,
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+        Err(error) => {
+            tracing::error!(target: Log::SlippiOnline, ?error, "[{}] Refusing to report match completion", fn_name);
+            return;
+        },
+    };
+
+    let match_id = c_str_to_string(match_id, fn_name, "match_id");
+
+    with::<SlippiEXIDevice, _>(instance_ptr, move |device| {
+        device.game_reporter.report_completion(match_id, end_mode);
+    });
 }
 
 /// Calls through to the `SlippiGameReporter` on the EXI device to report a
 /// match abandon event.
 #[no_mangle]
 pub extern "C" fn slprs_exi_device_report_match_abandonment(instance_ptr: usize, match_id: *const c_char) {
-    // Coerce the instances from the pointers. This is theoretically safe since we control
-    // the C++ side and can guarantee that the pointers are only owned
-    // by us, and are created/destroyed with the corresponding lifetimes.
-    let device = unsafe { Box::from_raw(instance_ptr as *mut SlippiEXIDevice) };
-
     let fn_name = "slprs_exi_device_report_match_abandonment";
     let match_id = c_str_to_string(match_id, fn_name, "match_id");
 
-    device.game_reporter.report_abandonment(match_id);
+    with::<SlippiEXIDevice, _>(instance_ptr, move |device| {
+        device.game_reporter.report_abandonment(match_id);
+    });
+}
+
+/// Calls through to `SlippiGameReporter::set_paused`. While paused, reports are still
+/// queued but aren't sent until reporting is resumed.
+#[no_mangle]
+pub extern "C" fn slprs_exi_device_set_reporter_paused(instance_ptr: usize, paused: bool) {
+    with::<SlippiEXIDevice, _>(instance_ptr, |device| {
+        device.game_reporter.set_paused(paused);
+    });
+}
+
+/// Returns how far along (0-100) the game reporter's ISO MD5 hash is, so the UI can show
+/// progress rather than appearing to hang on slow disks.
+#[no_mangle]
+pub extern "C" fn slprs_exi_device_get_iso_hash_progress(instance_ptr: usize) -> u8 {
+    with_returning::<SlippiEXIDevice, _, _>(instance_ptr, |device| device.game_reporter.iso_hash_progress())
+}
+
+/// Returns the ISO's MD5 hash once the game reporter has computed it, or a null pointer if
+/// it's still in progress (see `slprs_exi_device_get_iso_hash_progress`). This doesn't block.
+///
+/// A non-null return value _must_ be passed back to `slprs_exi_device_free_iso_hash` to free memory.
+#[no_mangle]
+pub extern "C" fn slprs_exi_device_get_iso_hash(instance_ptr: usize) -> *const c_char {
+    with_returning_or::<SlippiEXIDevice, _, _>(instance_ptr, ptr::null(), |device| match device.game_reporter.iso_hash() {
+        Some(hash) => CString::new(hash).expect("iso_hash CString failed").into_raw(),
+        None => ptr::null(),
+    })
+}
+
+/// Takes back ownership of a string returned from `slprs_exi_device_get_iso_hash` and drops it.
+#[no_mangle]
+pub extern "C" fn slprs_exi_device_free_iso_hash(ptr: *const c_char) {
+    if ptr.is_null() {
+        return;
+    }
+
+    unsafe {
+        let _hash = CString::from_raw(ptr as *mut _);
+    }
+}
+
+/// Calls through to `SlippiGameReporter::set_enabled`. While disabled, reports are logged
+/// rather than sent, which keeps local development builds from reporting real matches.
+#[no_mangle]
+pub extern "C" fn slprs_exi_device_set_reporter_enabled(instance_ptr: usize, enabled: bool) {
+    with::<SlippiEXIDevice, _>(instance_ptr, |device| {
+        device.game_reporter.set_enabled(enabled);
+    });
+}
+
+/// Mirrors `slippi_gg_api::HealthCheckState` (flattened with `HealthCheckStatus`) for
+/// cbindgen, which cannot see the types in the other crate.
+#[derive(Debug)]
+#[repr(C)]
+pub enum SlippiApiHealthStatus {
+    Ok = 0,
+    AuthFailure = 1,
+    NetworkFailure = 2,
+    ServerError = 3,
+    TimedOut = 4,
+    Pending = 5,
+    NotStarted = 6,
+}
+
+/// A C-compatible copy of `slippi_gg_api::HealthCheckState`.
+#[derive(Debug)]
+#[repr(C)]
+pub struct SlippiApiHealthCheck {
+    pub status: SlippiApiHealthStatus,
+
+    /// The round-trip time of the check in milliseconds, or `-1` if it hasn't finished or
+    /// the server never responded.
+    pub latency_ms: i64,
+}
+
+impl From<HealthCheckState> for SlippiApiHealthCheck {
+    fn from(state: HealthCheckState) -> Self {
+        let check = match state {
+            HealthCheckState::NotStarted => return Self::from(SlippiApiHealthStatus::NotStarted),
+            HealthCheckState::Pending => return Self::from(SlippiApiHealthStatus::Pending),
+            HealthCheckState::Finished(check) => check,
+        };
+
+        let status = match check.status {
+            HealthCheckStatus::Ok => SlippiApiHealthStatus::Ok,
+            HealthCheckStatus::AuthFailure => SlippiApiHealthStatus::AuthFailure,
+            HealthCheckStatus::NetworkFailure => SlippiApiHealthStatus::NetworkFailure,
+            HealthCheckStatus::ServerError => SlippiApiHealthStatus::ServerError,
+            HealthCheckStatus::TimedOut => SlippiApiHealthStatus::TimedOut,
+        };
+
+        Self {
+            status,
+            latency_ms: check.latency.map_or(-1, |latency| latency.as_millis() as i64),
+        }
+    }
+}
+
+impl From<SlippiApiHealthStatus> for SlippiApiHealthCheck {
+    fn from(status: SlippiApiHealthStatus) -> Self {
+        Self { status, latency_ms: -1 }
+    }
+}
+
+/// Starts a health check (a minimal GraphQL query) through the API client on the EXI device.
+/// This returns immediately; the check runs on a background thread, and its result can be
+/// read via `slprs_api_healthcheck_poll`. Starting a check while one is pending is a no-op.
+#[no_mangle]
+pub extern "C" fn slprs_api_healthcheck_start(exi_device_instance_ptr: usize) {
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        device.api_health.start(&device.api_client);
+    });
+}
+
+/// Returns the state of the most recent health check started via `slprs_api_healthcheck_start`:
+/// `Pending` while it's in flight, or where (if anywhere) connectivity broke once it's done,
+/// along with the round-trip latency. This is returned by value, so there's nothing to free,
+/// and it never blocks, so it's safe to poll from the UI thread.
+#[no_mangle]
+pub extern "C" fn slprs_api_healthcheck_poll(exi_device_instance_ptr: usize) -> SlippiApiHealthCheck {
+    with_returning_or::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, SlippiApiHealthStatus::NotStarted.into(), |device| {
+        device.api_health.state().into()
+    })
+}
+
+/// A C-compatible copy of `slippi_exi_device::SessionSummary`.
+///
+/// This must be passed back to `slprs_exi_device_free_session_stats` to free `now_playing`.
+#[derive(Debug)]
+#[repr(C)]
+pub struct RustSessionStats {
+    pub reports_sent: u32,
+    pub matches_played: u32,
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+    /// A description of the song the Jukebox is playing (see `slprs_jukebox_now_playing`), or
+    /// a null pointer if it's silent or not running.
+    pub now_playing: *const c_char,
+}
+
+impl Default for RustSessionStats {
+    fn default() -> Self {
+        Self {
+            reports_sent: 0,
+            matches_played: 0,
+            now_playing: ptr::null(),
+        }
+    }
+}
+
+/// Returns a summary of this session's activity from the EXI device at the provided pointer.
+///
+/// The returned struct _must_ be passed back to `slprs_exi_device_free_session_stats` to free memory.
+#[no_mangle]
+pub extern "C" fn slprs_exi_device_get_session_stats(exi_device_instance_ptr: usize) -> RustSessionStats {
+    with_returning::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, |device| {
+        let summary = device.session_summary();
+
+        let now_playing = match summary.now_playing {
+            Some(song) => CString::new(song.to_string())
+                .expect("Jukebox now playing CString failed")
+                .into_raw() as *const c_char,
+
+            None => ptr::null(),
+        };
+
+        RustSessionStats {
+            reports_sent: summary.reports_sent,
+            matches_played: summary.matches_played,
+            now_playing,
+        }
+    })
+}
+
+/// Takes back ownership of the strings in a `RustSessionStats` returned from
+/// `slprs_exi_device_get_session_stats` and drops them.
+#[no_mangle]
+pub extern "C" fn slprs_exi_device_free_session_stats(stats: RustSessionStats) {
+    if stats.now_playing.is_null() {
+        return;
+    }
+
+    unsafe {
+        let _now_playing = CString::from_raw(stats.now_playing as *mut _);
+    }
 }
 
 /// Calls through to `SlippiGameReporter::push_replay_data`.
@@ -204,41 +380,41 @@ pub extern "C" fn slprs_exi_device_reporter_push_replay_data(instance_ptr: usize
     // doesn't need to deal with anything C-ish.
     let slice = unsafe { std::slice::from_raw_parts(data, length as usize) };
 
-    // Coerce the instances from the pointers. This is theoretically safe since we control
-    // the C++ side and can guarantee that the pointers are only owned
-    // by us, and are created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(instance_ptr as *mut SlippiEXIDevice) };
-
-    device.game_reporter.push_replay_data(slice);
-
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+    with::<SlippiEXIDevice, _>(instance_ptr, |device| {
+        device.game_reporter.push_replay_data(slice);
+    });
 }
 
 /// Configures the Jukebox process. This needs to be called after the EXI device is created
 /// in order for certain pieces of Dolphin to be properly initalized; this may change down
 /// the road though and is not set in stone.
+///
+/// `fade_in_ms` is how long newly started songs take to ramp up to full volume; `0` starts
+/// them at full volume immediately. `idle_timeout_secs` is how long the Jukebox can go without
+/// playing music before it releases the audio device; `0` (the default) never releases it.
 #[no_mangle]
 pub extern "C" fn slprs_exi_device_configure_jukebox(
     exi_device_instance_ptr: usize,
     is_enabled: bool,
     initial_dolphin_system_volume: u8,
     initial_dolphin_music_volume: u8,
+    fade_in_ms: u32,
+    idle_timeout_secs: u32,
 ) {
-    // Coerce the instance from the pointer. This is theoretically safe since we control
-    // the C++ side and can guarantee that the `exi_device_instance_ptr` is only owned
-    // by the C++ EXI device, and is created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(exi_device_instance_ptr as *mut SlippiEXIDevice) };
-
     let jukebox_config = match is_enabled {
         true => JukeboxConfiguration::Start {
             initial_dolphin_system_volume,
             initial_dolphin_music_volume,
+            fade_in: Duration::from_millis(fade_in_ms.into()),
+            idle_timeout: match idle_timeout_secs {
+                0 => None,
+                secs => Some(Duration::from_secs(secs.into())),
+            },
         },
         false => JukeboxConfiguration::Stop,
     };
-    device.configure_jukebox(jukebox_config);
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, move |device| {
+        device.configure_jukebox(jukebox_config);
+    });
 }
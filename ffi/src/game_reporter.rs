@@ -3,7 +3,8 @@ use std::ffi::c_char;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-use slippi_game_reporter::{GameReport, OnlinePlayMode as ReporterOnlinePlayMode, PlayerReport};
+use dolphin_integrations::Log;
+use slippi_game_reporter::{GameReport, OnlinePlayMode, PlayerReport};
 
 use crate::{c_str_to_string, with};
 
@@ -11,6 +12,10 @@ use crate::{c_str_to_string, with};
 /// to appease cbindgen, which cannot see the type from the other module for
 /// inspection.
 ///
+/// The FFI functions accept the raw byte and validate it on the Rust side (a bad value
+/// received as a Rust enum would be undefined behavior), so this is only exported to keep
+/// the named values available to the C++ side.
+///
 /// This enum will likely go away as things move towards Rust, since it's effectively
 /// just C FFI glue code.
 #[derive(Debug)]
@@ -59,11 +64,14 @@ pub extern "C" fn slprs_player_report_create(
 ///
 /// This is expected to ultimately be passed to the game reporter, which will handle
 /// destruction and cleanup.
+///
+/// If `online_mode` is not a known `SlippiMatchmakingOnlinePlayMode` value, this logs and
+/// returns `0` instead. The other game report functions treat a `0` pointer as a no-op.
 #[no_mangle]
 pub extern "C" fn slprs_game_report_create(
     uid: *const c_char,
     play_key: *const c_char,
-    online_mode: SlippiMatchmakingOnlinePlayMode,
+    online_mode: u8,
     match_id: *const c_char,
     duration_frames: u32,
     game_index: u32,
@@ -74,6 +82,16 @@ pub extern "C" fn slprs_game_report_create(
     stage_id: i32,
 ) -> usize {
     let fn_name = "slprs_game_report_create";
+
+    let online_mode = match OnlinePlayMode::try_from(online_mode) {
+        Ok(online_mode) => online_mode,
+
+        Err(error) => {
+            tracing::error!(target: Log::SlippiOnline, ?error, "[{}] Refusing to create game report", fn_name);
+            return 0;
+        },
+    };
+
     let uid = c_str_to_string(uid, fn_name, "user_id");
     let play_key = c_str_to_string(play_key, fn_name, "play_key");
     let match_id = c_str_to_string(match_id, fn_name, "match_id");
@@ -82,13 +100,7 @@ pub extern "C" fn slprs_game_report_create(
         uid,
         play_key,
 
-        online_mode: match online_mode {
-            SlippiMatchmakingOnlinePlayMode::Ranked => ReporterOnlinePlayMode::Ranked,
-            SlippiMatchmakingOnlinePlayMode::Unranked => ReporterOnlinePlayMode::Unranked,
-            SlippiMatchmakingOnlinePlayMode::Direct => ReporterOnlinePlayMode::Direct,
-            SlippiMatchmakingOnlinePlayMode::Teams => ReporterOnlinePlayMode::Teams,
-        },
-
+        online_mode,
         match_id,
         attempts: 0,
         duration_frames,
@@ -111,11 +123,21 @@ pub extern "C" fn slprs_game_report_create(
 /// `GameReport` at the corresponding pointer.
 #[no_mangle]
 pub extern "C" fn slprs_game_report_add_player_report(instance_ptr: usize, player_report_instance_ptr: usize) {
+    if player_report_instance_ptr == 0 {
+        tracing::warn!(target: Log::SlippiOnline, "Ignoring null player report");
+        return;
+    }
+
     // Coerce the instance from the pointer. This is theoretically safe since we control
     // the C++ side and can guarantee that the `game_report_instance_ptr` is only owned
     // by us, and is created/destroyed with the corresponding lifetimes.
     let player_report = unsafe { Box::from_raw(player_report_instance_ptr as *mut PlayerReport) };
 
+    // A `0` report means creation was refused; let the player report drop.
+    if instance_ptr == 0 {
+        return;
+    }
+
     with::<GameReport, _>(instance_ptr, move |report| {
         report.players.push(*player_report);
     });
@@ -1,82 +1,174 @@
+use std::ffi::{c_char, CString};
+use std::ptr;
+
 use slippi_exi_device::SlippiEXIDevice;
 use slippi_jukebox::VolumeControl;
 
+use crate::{with, with_returning, with_returning_or};
+
 /// Calls through to `Jukebox::start_song`.
 #[no_mangle]
 pub extern "C" fn slprs_jukebox_start_song(exi_device_instance_ptr: usize, hps_offset: u64, hps_length: usize) {
-    // Coerce the instance from the pointer. This is theoretically safe since we control
-    // the C++ side and can guarantee that the `exi_device_instance_ptr` is only owned
-    // by the C++ EXI device, and is created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(exi_device_instance_ptr as *mut SlippiEXIDevice) };
-
-    if let Some(jukebox) = device.jukebox.as_mut() {
-        jukebox.start_song(hps_offset, hps_length);
-    }
-
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.start_song(hps_offset, hps_length);
+        }
+    });
 }
 
 /// Calls through to `Jukebox::stop_music`.
 #[no_mangle]
 pub extern "C" fn slprs_jukebox_stop_music(exi_device_instance_ptr: usize) {
-    // Coerce the instance from the pointer. This is theoretically safe since we control
-    // the C++ side and can guarantee that the `exi_device_instance_ptr` is only owned
-    // by the C++ EXI device, and is created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(exi_device_instance_ptr as *mut SlippiEXIDevice) };
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.stop_music();
+        }
+    });
+}
 
-    if let Some(jukebox) = device.jukebox.as_mut() {
-        jukebox.stop_music();
-    }
+/// Calls through to `Jukebox::pause`.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_pause(exi_device_instance_ptr: usize) {
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.pause();
+        }
+    });
+}
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+/// Calls through to `Jukebox::resume`.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_resume(exi_device_instance_ptr: usize) {
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.resume();
+        }
+    });
 }
 
-/// Calls through to `Jukebox::set_volume` with the Melee volume control.
+/// Calls through to `Jukebox::mute`.
 #[no_mangle]
-pub extern "C" fn slprs_jukebox_set_melee_music_volume(exi_device_instance_ptr: usize, volume: u8) {
-    // Coerce the instance from the pointer. This is theoretically safe since we control
-    // the C++ side and can guarantee that the `exi_device_instance_ptr` is only owned
-    // by the C++ EXI device, and is created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(exi_device_instance_ptr as *mut SlippiEXIDevice) };
+pub extern "C" fn slprs_jukebox_mute(exi_device_instance_ptr: usize) {
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.mute();
+        }
+    });
+}
 
-    if let Some(jukebox) = device.jukebox.as_mut() {
-        jukebox.set_volume(VolumeControl::Melee, volume);
-    }
+/// Calls through to `Jukebox::unmute`.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_unmute(exi_device_instance_ptr: usize) {
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.unmute();
+        }
+    });
+}
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+/// Calls through to `Jukebox::reinit_audio`.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_reinit_audio(exi_device_instance_ptr: usize) {
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.reinit_audio();
+        }
+    });
+}
+
+/// Calls through to `Jukebox::set_volume` with the Melee volume control.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_set_melee_music_volume(exi_device_instance_ptr: usize, volume: u8) {
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.set_volume(VolumeControl::Melee, volume);
+        }
+    });
 }
 
 /// Calls through to `Jukebox::set_volume` with the DolphinSystem volume control.
 #[no_mangle]
 pub extern "C" fn slprs_jukebox_set_dolphin_system_volume(exi_device_instance_ptr: usize, volume: u8) {
-    // Coerce the instance from the pointer. This is theoretically safe since we control
-    // the C++ side and can guarantee that the `exi_device_instance_ptr` is only owned
-    // by the C++ EXI device, and is created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(exi_device_instance_ptr as *mut SlippiEXIDevice) };
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.set_volume(VolumeControl::DolphinSystem, volume);
+        }
+    });
+}
+
+/// Calls through to `Jukebox::set_volume` with the DolphinMusic volume control.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_set_dolphin_music_volume(exi_device_instance_ptr: usize, volume: u8) {
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        if let Some(jukebox) = device.jukebox.as_mut() {
+            jukebox.set_volume(VolumeControl::DolphinMusic, volume);
+        }
+    });
+}
+
+/// Returns whether the Jukebox on the EXI device is currently running.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_is_active(exi_device_instance_ptr: usize) -> bool {
+    with_returning::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, |device| device.is_jukebox_active())
+}
+
+/// Returns the reason the Jukebox last failed to start (e.g, an unsupported ISO), or a null
+/// pointer if it hasn't failed.
+///
+/// A non-null return value _must_ be passed back to `slprs_jukebox_free_last_error` to free memory.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_last_error(exi_device_instance_ptr: usize) -> *const c_char {
+    with_returning_or::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, ptr::null(), |device| {
+        match device.jukebox_last_error() {
+            Some(error) => CString::new(error.to_string())
+                .expect("Jukebox error CString failed")
+                .into_raw(),
 
-    if let Some(jukebox) = device.jukebox.as_mut() {
-        jukebox.set_volume(VolumeControl::DolphinSystem, volume);
+            None => ptr::null(),
+        }
+    })
+}
+
+/// Takes back ownership of a string returned from `slprs_jukebox_last_error` and drops it.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_free_last_error(ptr: *const c_char) {
+    if ptr.is_null() {
+        return;
     }
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+    unsafe {
+        let _error = CString::from_raw(ptr as *mut _);
+    }
 }
 
-/// Calls through to `Jukebox::set_volume` with the DolphinMusic volume control.
+/// Returns a description of the song the Jukebox is currently playing, or a null pointer if
+/// it's silent (or not running).
+///
+/// There's no track list to resolve stage names from, so this describes the song by where its
+/// hps file lives (e.g, `hps offset: 0x1a2b3c, length: 123456`) rather than by stage name.
+///
+/// A non-null return value _must_ be passed back to `slprs_jukebox_free_now_playing` to free memory.
 #[no_mangle]
-pub extern "C" fn slprs_jukebox_set_dolphin_music_volume(exi_device_instance_ptr: usize, volume: u8) {
-    // Coerce the instance from the pointer. This is theoretically safe since we control
-    // the C++ side and can guarantee that the `exi_device_instance_ptr` is only owned
-    // by the C++ EXI device, and is created/destroyed with the corresponding lifetimes.
-    let mut device = unsafe { Box::from_raw(exi_device_instance_ptr as *mut SlippiEXIDevice) };
+pub extern "C" fn slprs_jukebox_now_playing(exi_device_instance_ptr: usize) -> *const c_char {
+    with_returning_or::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, ptr::null(), |device| {
+        match device.jukebox_now_playing() {
+            Some(song) => CString::new(song.to_string())
+                .expect("Jukebox now playing CString failed")
+                .into_raw(),
+
+            None => ptr::null(),
+        }
+    })
+}
 
-    if let Some(jukebox) = device.jukebox.as_mut() {
-        jukebox.set_volume(VolumeControl::DolphinMusic, volume);
+/// Takes back ownership of a string returned from `slprs_jukebox_now_playing` and drops it.
+#[no_mangle]
+pub extern "C" fn slprs_jukebox_free_now_playing(ptr: *const c_char) {
+    if ptr.is_null() {
+        return;
     }
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(device);
+    unsafe {
+        let _song = CString::from_raw(ptr as *mut _);
+    }
 }
@@ -5,7 +5,9 @@
 //! This library auto-generates C headers on build, and Slippi Dolphin is pre-configured
 //! to locate these headers and link the entire dylib.
 
+use std::any::Any;
 use std::ffi::{c_char, CStr};
+use std::panic::{self, AssertUnwindSafe};
 
 use dolphin_integrations::Log;
 
@@ -17,6 +19,9 @@ pub mod user;
 
 /// A small helper method for moving in and out of our known types.
 ///
+/// If `handler` panics, the panic is logged and swallowed here rather than unwinding across
+/// the FFI boundary into C++. The instance itself is never dropped by this call.
+///
 /// > This method operates in `unsafe` territory as it's operating on pointers owned by the C++
 /// > side. That said, this isn't really a "library" in the traditional sense - we control the C++
 /// > side and can verify the few places where these pointers are passed over. We silo the `unsafe`
@@ -29,20 +34,48 @@ pub(crate) fn with<T, F>(instance_ptr: usize, handler: F)
 where
     F: FnOnce(&mut T),
 {
+    // A null instance (e.g, if C++ never called the corresponding create function) can't be
+    // dereferenced, so treat the call as a no-op rather than invoking undefined behavior.
+    if instance_ptr == 0 {
+        log_null_instance::<T>();
+        return;
+    }
+
     // Coerce the instance from the pointer. This is theoretically safe since we control
     // the C++ side and can guarantee that the `instance_ptr` is only owned
     // by us, and is created/destroyed with the corresponding lifetimes.
-    let mut instance = unsafe { Box::from_raw(instance_ptr as *mut T) };
-
-    handler(&mut instance);
+    //
+    // We borrow rather than round-tripping through a `Box`, so there's no window where
+    // an unwinding handler could drop the instance out from under the C++ side.
+    let instance = unsafe { &mut *(instance_ptr as *mut T) };
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(instance);
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| handler(instance))) {
+        tracing::error!(
+            target: Log::SlippiOnline,
+            error = panic_message(&*payload),
+            "FFI handler panicked, refusing to unwind into C++"
+        );
+    }
 }
 
 /// A small helper method for moving in and out of our known types.
 ///
-/// This variant can be used to return a value from within a handler.
+/// This variant can be used to return a value from within a handler. Like `with`, a panic in
+/// `handler` is logged rather than unwinding into C++, and the instance itself is never dropped
+/// by this call.
+///
+/// A null instance pointer, or a panicking `handler`, returns `R::default()`. Use
+/// `with_returning_or` when there's no sensible default (e.g, for raw pointers).
+pub(crate) fn with_returning<T, F, R>(instance_ptr: usize, handler: F) -> R
+where
+    F: FnOnce(&mut T) -> R,
+    R: Default,
+{
+    with_returning_or(instance_ptr, R::default(), handler)
+}
+
+/// Like `with_returning`, but returns `fallback` if the instance pointer is null (without
+/// calling `handler`) or if `handler` panics.
 ///
 /// > This method operates in `unsafe` territory as it's operating on pointers owned by the C++
 /// > side. That said, this isn't really a "library" in the traditional sense - we control the C++
@@ -52,21 +85,56 @@ where
 /// >
 /// > This method has internal documentation surrounding safety assumptions to explain the
 /// > reasoning further.
-pub(crate) fn with_returning<T, F, R>(instance_ptr: usize, handler: F) -> R
+pub(crate) fn with_returning_or<T, F, R>(instance_ptr: usize, fallback: R, handler: F) -> R
 where
     F: FnOnce(&mut T) -> R,
 {
+    if instance_ptr == 0 {
+        log_null_instance::<T>();
+        return fallback;
+    }
+
     // Coerce the instance from the pointer. This is theoretically safe since we control
     // the C++ side and can guarantee that the `instance_ptr` is only owned
     // by us, and is created/destroyed with the corresponding lifetimes.
-    let mut instance = unsafe { Box::from_raw(instance_ptr as *mut T) };
+    let instance = unsafe { &mut *(instance_ptr as *mut T) };
 
-    let ret = handler(&mut instance);
+    match panic::catch_unwind(AssertUnwindSafe(|| handler(instance))) {
+        Ok(ret) => ret,
 
-    // Fall back into a raw pointer so Rust doesn't obliterate the object.
-    let _leak = Box::into_raw(instance);
+        Err(payload) => {
+            tracing::error!(
+                target: Log::SlippiOnline,
+                error = panic_message(&*payload),
+                "FFI handler panicked, refusing to unwind into C++"
+            );
 
-    ret
+            fallback
+        },
+    }
+}
+
+/// Logs that an FFI call was handed a null `T` instance, which usually means the C++ side
+/// is calling in before the corresponding create function has run.
+fn log_null_instance<T>() {
+    tracing::error!(
+        target: Log::SlippiOnline,
+        instance = std::any::type_name::<T>(),
+        "FFI call received a null instance pointer, ignoring"
+    );
+}
+
+/// Pulls a loggable message out of a panic payload, which is usually either a `&str` or
+/// a `String` depending on how `panic!` was invoked.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message;
+    }
+
+    match payload.downcast_ref::<String>() {
+        Some(message) => message.as_str(),
+        None => "Unknown panic payload",
+    }
 }
 
 /// A helper function for converting c str types to Rust ones with
@@ -104,3 +172,55 @@ pub(crate) fn c_str_to_string(string: *const c_char, fn_label: &str, err_label:
         },
     }
 }
+
+/// A non-panicking counterpart to `c_str_to_string`, for strings we can't fully trust to be
+/// valid UTF-8 (e.g, user-provided paths). Null pointers and invalid UTF-8 are logged and
+/// yield `None`, so callers can degrade gracefully instead of unwinding into C++.
+pub(crate) fn try_c_str_to_string(string: *const c_char, fn_label: &str, err_label: &str) -> Option<String> {
+    if string.is_null() {
+        tracing::error!(target: Log::SlippiOnline, "[{}] Received null pointer for {}", fn_label, err_label);
+        return None;
+    }
+
+    // Same reasoning as `c_str_to_string` - we control what's being passed over.
+    let slice = unsafe { CStr::from_ptr(string) };
+
+    match slice.to_str() {
+        Ok(s) => Some(s.to_string()),
+
+        Err(e) => {
+            tracing::error!(
+                target: Log::SlippiOnline,
+                error = ?e,
+                "[{}] Failed to bridge {}, ignoring it",
+                fn_label,
+                err_label
+            );
+
+            None
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panicking_handlers_return_the_fallback() {
+        let mut instance = 1u32;
+        let instance_ptr = &mut instance as *mut u32 as usize;
+
+        let value = with_returning_or::<u32, _, _>(instance_ptr, 7, |_| -> u32 { panic!("handler failed") });
+        assert_eq!(value, 7);
+
+        let value = with_returning::<u32, _, _>(instance_ptr, |_| -> u32 { panic!("handler failed") });
+        assert_eq!(value, 0);
+
+        // Handlers that don't panic are unaffected.
+        let value = with_returning_or::<u32, _, _>(instance_ptr, 7, |instance| *instance + 1);
+        assert_eq!(value, 2);
+
+        assert_eq!(with_returning_or::<u32, _, _>(0, 7, |instance| *instance), 7);
+    }
+}
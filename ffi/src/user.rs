@@ -1,8 +1,9 @@
 use std::ffi::{c_char, c_int, CString};
+use std::ptr;
 
 use slippi_exi_device::SlippiEXIDevice;
 
-use crate::{c_str_to_string, with, with_returning};
+use crate::{c_str_to_string, with, with_returning, with_returning_or};
 
 /// Instructs the `UserManager` on the EXI Device at the provided pointer to attempt
 /// authentication. This runs synchronously on whatever thread it's called on.
@@ -37,6 +38,31 @@ pub extern "C" fn slprs_user_listen_for_login(exi_device_instance_ptr: usize) {
     });
 }
 
+/// Instructs the `UserManager` on the EXI Device at the provided pointer to re-fetch the
+/// current user's info from the server. This runs on a background thread.
+#[no_mangle]
+pub extern "C" fn slprs_user_refresh(exi_device_instance_ptr: usize) {
+    with::<SlippiEXIDevice, _>(exi_device_instance_ptr, |device| {
+        device.user_manager.refresh();
+    });
+}
+
+/// Checks whether `user.json` exists but keeps failing to be read (e.g, it's locked by another
+/// process), so the UI can tell the user something is blocking the file.
+#[no_mangle]
+pub extern "C" fn slprs_user_get_is_user_json_blocked(exi_device_instance_ptr: usize) -> bool {
+    with_returning::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, |device| device.user_manager.is_user_json_blocked())
+}
+
+/// Checks whether the folder holding `user.json` could be written to, so the UI can warn the
+/// user that anything saved there will only last for this session.
+#[no_mangle]
+pub extern "C" fn slprs_user_get_is_config_folder_writable(exi_device_instance_ptr: usize) -> bool {
+    with_returning::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, |device| {
+        device.user_manager.is_config_folder_writable()
+    })
+}
+
 /// Instructs the `UserManager` on the EXI Device at the provided pointer to sign the user out.
 /// This will delete the `user.json` file from the underlying filesystem.
 #[no_mangle]
@@ -84,9 +110,11 @@ pub struct RustUserInfo {
 /// This involves slightly more allocations than ideal, so this shouldn't be called in a hot path.
 /// Over time this issue will not matter as once Matchmaking is moved to Rust we can share things
 /// quite easily.
+///
+/// Returns a null pointer if the EXI device pointer is null.
 #[no_mangle]
 pub extern "C" fn slprs_user_get_info(exi_device_instance_ptr: usize) -> *mut RustUserInfo {
-    with_returning::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, |device| {
+    with_returning_or::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, ptr::null_mut(), |device| {
         let user_info = device.user_manager.get(|user| {
             let uid = CString::new(user.uid.as_str()).expect("uid CString failed").into_raw();
 
@@ -188,9 +216,10 @@ impl RustChatMessages {
 /// Returns a C-compatible struct containing the chat message options for the current user.
 ///
 /// The return value of this _must_ be passed back to `slprs_user_free_messages` to free memory.
+/// A null EXI device pointer returns a null pointer.
 #[no_mangle]
 pub extern "C" fn slprs_user_get_messages(exi_device_instance_ptr: usize) -> *mut RustChatMessages {
-    with_returning::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, |device| {
+    with_returning_or::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, ptr::null_mut(), |device| {
         let messages = device.user_manager.get(|user| {
             Box::new(RustChatMessages::from(match &user.chat_messages {
                 Some(messages) => messages,
@@ -205,9 +234,10 @@ pub extern "C" fn slprs_user_get_messages(exi_device_instance_ptr: usize) -> *mu
 /// Returns a C-compatible struct containing the default chat message options.
 ///
 /// The return value of this _must_ be passed back to `slprs_user_free_messages` to free memory.
+/// A null EXI device pointer returns a null pointer.
 #[no_mangle]
 pub extern "C" fn slprs_user_get_default_messages(exi_device_instance_ptr: usize) -> *mut RustChatMessages {
-    with_returning::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, |_device| {
+    with_returning_or::<SlippiEXIDevice, _, _>(exi_device_instance_ptr, ptr::null_mut(), |_device| {
         let messages = Box::new(RustChatMessages::from(&slippi_user::DEFAULT_CHAT_MESSAGES));
         Box::into_raw(messages)
     })
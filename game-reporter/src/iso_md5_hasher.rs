@@ -2,12 +2,14 @@
 //! be called from a background thread due to processing time.
 
 use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 
-use chksum::chksum;
-use chksum::hash::MD5;
+use md5::{Digest, Md5};
 
-use dolphin_integrations::{Color, Dolphin, Duration, Log};
+use dolphin_integrations::{Color, Duration, Log, OsdSink};
 
 /// ISO hashes that are known to cause problems. We alert the player
 /// if we detect that they're running one.
@@ -18,17 +20,27 @@ const KNOWN_DESYNC_ISOS: [&'static str; 4] = [
     "9bb3e275e77bb1a160276f2330f93931",
 ];
 
+/// How much of the ISO is read (and hashed) at a time.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
 /// Computes an MD5 hash of the ISO at `iso_path` and writes it back to the value
-/// behind `iso_hash`.
+/// behind `iso_hash`. `progress` is updated with the percentage hashed so far as
+/// we go, since this can take a while on slow disks.
 ///
 /// This function is currently more defensive than it probably needs to be, but while
 /// we move things into Rust I'd like to reduce the chances of anything panic'ing back
 /// into C++ since that can produce undefined behavior. This just handles every possible
 /// failure gracefully - however seemingly rare - and simply logs the error.
-pub fn run(iso_hash: Arc<Mutex<String>>, iso_path: String) {
-    let digest = match File::open(&iso_path) {
-        Ok(file) => match chksum::<MD5, _>(file) {
-            Ok(digest) => digest,
+pub fn run(
+    iso_hash: Arc<Mutex<String>>,
+    iso_progress: Arc<AtomicU8>,
+    iso_path: String,
+    desync_iso_list_path: Option<PathBuf>,
+    osd: Arc<dyn OsdSink>,
+) {
+    let hash = match File::open(&iso_path) {
+        Ok(file) => match hash_with_progress(file, &iso_progress) {
+            Ok(hash) => hash,
 
             Err(error) => {
                 tracing::error!(target: Log::SlippiOnline, ?error, "Unable to produce ISO MD5 Hash");
@@ -44,9 +56,98 @@ pub fn run(iso_hash: Arc<Mutex<String>>, iso_path: String) {
         },
     };
 
-    let hash = format!("{:x}", digest);
+    let extra_desync_isos = match desync_iso_list_path {
+        Some(path) => load_desync_iso_list(&path),
+        None => Vec::new(),
+    };
+
+    check_for_desync_iso(&hash, &extra_desync_isos, osd.as_ref());
+
+    match iso_hash.lock() {
+        Ok(mut iso_hash) => {
+            *iso_hash = hash;
+        },
+
+        Err(error) => {
+            tracing::error!(target: Log::SlippiOnline, ?error, "Unable to lock iso_hash");
+        },
+    };
+}
+
+/// Streams `file` through MD5 in chunks, publishing the percentage hashed to `progress`.
+fn hash_with_progress(mut file: File, progress: &AtomicU8) -> io::Result<String> {
+    let total = file.metadata()?.len();
+    let mut hasher = Md5::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut hashed = 0u64;
+
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+
+        hasher.update(&buffer[..read]);
+        hashed += read as u64;
+        progress.store(percent(hashed, total), Ordering::Relaxed);
+    }
+
+    progress.store(100, Ordering::Relaxed);
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Returns how far through `total` we are, as a percentage. Empty files are trivially done.
+fn percent(done: u64, total: u64) -> u8 {
+    match total {
+        0 => 100,
+        total => (done.min(total) * 100 / total) as u8,
+    }
+}
+
+/// Loads additional known desync ISO hashes from the file at `path`, so new ones can be
+/// shipped without a new build. The file can either be a JSON array of hashes, or have
+/// one hash per line (blank lines and lines starting with `#` are skipped).
+///
+/// A missing or malformed file logs a warning and yields no extra hashes, leaving just the
+/// built-in list.
+fn load_desync_iso_list(path: &Path) -> Vec<String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+
+        Err(error) => {
+            tracing::warn!(target: Log::SlippiOnline, ?error, ?path, "Unable to read desync ISO list");
+            return Vec::new();
+        },
+    };
+
+    if !contents.trim_start().starts_with('[') {
+        return contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect();
+    }
+
+    match serde_json::from_str::<Vec<String>>(&contents) {
+        Ok(hashes) => hashes.iter().map(|hash| hash.trim().to_lowercase()).collect(),
 
-    if !KNOWN_DESYNC_ISOS.contains(&hash.as_str()) {
+        Err(error) => {
+            tracing::warn!(target: Log::SlippiOnline, ?error, ?path, "Unable to parse desync ISO list");
+            Vec::new()
+        },
+    }
+}
+
+/// Logs the ISO hash, warning the player via `osd` if it's one that's known to cause desyncs
+/// (either built-in, or in `extra_desync_isos`).
+fn check_for_desync_iso(hash: &str, extra_desync_isos: &[String], osd: &dyn OsdSink) {
+    let is_known_desync = KNOWN_DESYNC_ISOS.contains(&hash) || extra_desync_isos.iter().any(|known| known == hash);
+
+    if !is_known_desync {
         tracing::info!(target: Log::SlippiOnline, iso_md5_hash = ?hash);
     } else {
         // Dump it into the logs as well in case we're ever looking at a user's
@@ -61,20 +162,76 @@ pub fn run(iso_hash: Arc<Mutex<String>>, iso_path: String) {
         // why they were there - some weird string parsing issue...?
         //
         // Settle on 2 (4 before) as a middle ground I guess.
-        Dolphin::add_osd_message(
+        osd.add_message(
             Color::Red,
             Duration::Custom(20000),
             "\n\nCAUTION: You are using an ISO that is known to cause desyncs",
         );
     }
+}
 
-    match iso_hash.lock() {
-        Ok(mut iso_hash) => {
-            *iso_hash = hash;
-        },
+#[cfg(test)]
+mod tests {
+    use dolphin_integrations::RecordingOsd;
 
-        Err(error) => {
-            tracing::error!(target: Log::SlippiOnline, ?error, "Unable to lock iso_hash");
-        },
-    };
+    use super::*;
+
+    #[test]
+    fn warns_about_known_desync_isos() {
+        let osd = RecordingOsd::default();
+        check_for_desync_iso(KNOWN_DESYNC_ISOS[0], &[], &osd);
+
+        let messages = osd.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, Color::Red);
+        assert!(messages[0].2.contains("known to cause desyncs"));
+    }
+
+    #[test]
+    fn flags_hashes_from_override_list() {
+        let hash = "0123456789abcdef0123456789abcdef";
+        let path = std::env::temp_dir().join(format!("slippi-desync-isos-{}.txt", std::process::id()));
+
+        std::fs::write(&path, format!("# Added after release\n\n{}\n", hash.to_uppercase())).unwrap();
+        let extra = load_desync_iso_list(&path);
+
+        std::fs::write(&path, format!("[\"{hash}\"]")).unwrap();
+        assert_eq!(load_desync_iso_list(&path), extra);
+
+        std::fs::write(&path, "[not json").unwrap();
+        assert!(load_desync_iso_list(&path).is_empty());
+        let _ = std::fs::remove_file(&path);
+
+        let osd = RecordingOsd::default();
+        check_for_desync_iso(hash, &[], &osd);
+        assert!(osd.messages().is_empty());
+
+        check_for_desync_iso(hash, &extra, &osd);
+        assert_eq!(osd.messages().len(), 1);
+    }
+
+    #[test]
+    fn hashes_files_and_reports_progress() {
+        let path = std::env::temp_dir().join(format!("slippi-iso-md5-{}", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+
+        let progress = AtomicU8::new(0);
+        let hash = hash_with_progress(File::open(&path).unwrap(), &progress).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(hash, "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(progress.load(Ordering::Relaxed), 100);
+
+        assert_eq!(percent(0, 200), 0);
+        assert_eq!(percent(50, 200), 25);
+        assert_eq!(percent(0, 0), 100);
+    }
+
+    #[test]
+    fn stays_quiet_for_other_isos() {
+        let osd = RecordingOsd::default();
+        check_for_desync_iso("d41d8cd98f00b204e9800998ecf8427e", &[], &osd);
+
+        assert!(osd.messages().is_empty());
+    }
 }
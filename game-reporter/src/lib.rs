@@ -1,14 +1,17 @@
 //! This could be rewritten down the road, but the goal is a 1:1 port right now,
 //! not to rewrite the universe.
 
+use std::collections::HashSet;
 use std::ops::Deref;
+use std::path::PathBuf;
 use std::sync::mpsc::{self, Sender};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
+use std::time::{Duration, Instant};
 
-use dolphin_integrations::Log;
-use slippi_gg_api::APIClient;
+use dolphin_integrations::{DolphinOsd, Log, OsdSink};
+use slippi_gg_api::{APIClient, GRAPHQL_URL};
 use slippi_user::UserManager;
 
 mod iso_md5_hasher;
@@ -16,14 +19,146 @@ mod iso_md5_hasher;
 mod queue;
 use queue::GameReporterQueue;
 
+mod replay_ring;
+use replay_ring::ReplayRing;
+
+mod score;
+pub use score::set_score;
+
 mod types;
-pub use types::{GameReport, OnlinePlayMode, PlayerReport};
+pub use types::{
+    GameEndMode, GameReport, InvalidGameReport, InvalidPlayerReport, OnlinePlayMode, PlayerReport, UnknownGameEndMode,
+    UnknownOnlinePlayMode,
+};
+
+/// The largest (compressed) replay we'll attempt to upload by default. This matches the
+/// content length range the upload endpoint accepts.
+const DEFAULT_MAX_UPLOAD_SIZE: usize = 10_000_000;
+
+/// The largest raw replay we'll bother compressing by default. Replays compress well, so this
+/// is deliberately a good deal larger than `DEFAULT_MAX_UPLOAD_SIZE`.
+const DEFAULT_MAX_REPLAY_SIZE: usize = 50_000_000;
+
+/// How many times a report should attempt to send, unless configured otherwise.
+const DEFAULT_MAX_REPORT_ATTEMPTS: i32 = 5;
+
+/// How long shutdown waits on queued reports by default before giving up on them.
+const DEFAULT_SHUTDOWN_DEADLINE: Duration = Duration::from_secs(3);
+
+/// Extra time given to the processing thread past the shutdown deadline, so a request that
+/// was cut off right at the deadline has a chance to wrap up before we stop waiting.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// How often shutdown checks whether the processing thread has finished.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Configuration for a `GameReporter`.
+///
+/// The `Default` implementation leaves both timeouts unset, in which case requests
+/// fall back to the timeout configured on the shared `APIClient`, and renders messages
+/// through Dolphin.
+#[derive(Clone, Debug)]
+pub struct GameReporterConfig {
+    /// Timeout applied to each individual report send attempt. Keeping this shorter than the
+    /// client timeout lets the queue move on to its retry backoff sooner when the server stalls.
+    pub report_timeout: Option<Duration>,
+
+    /// Timeout applied to replay uploads, which are considerably larger than reports.
+    pub upload_timeout: Option<Duration>,
+
+    /// Compressed replays larger than this (in bytes) are not uploaded. The game report
+    /// itself is still sent, and the user is told (once per session) that it was skipped.
+    pub max_upload_size: usize,
+
+    /// Replays whose raw (uncompressed) size exceeds this are skipped before compression, so
+    /// that an obviously oversized replay doesn't cost a compression pass just to be rejected.
+    pub max_replay_size: usize,
+
+    /// An optional file of additional known desync ISO hashes, checked alongside the built-in
+    /// list. See `iso_md5_hasher` for the accepted formats.
+    pub desync_iso_list_path: Option<PathBuf>,
+
+    /// Where user-facing messages (failed reports, desync ISOs, etc) get sent.
+    pub osd: Arc<dyn OsdSink>,
+
+    /// Decides whether match abandonments get reported.
+    pub abandonment_policy: AbandonmentPolicy,
+
+    /// The GraphQL endpoint that reports, completions and abandonments are sent to.
+    pub graphql_url: String,
+
+    /// How many times a report is attempted before it's dropped from the queue.
+    pub max_report_attempts: i32,
+
+    /// The most time shutdown spends trying to send queued reports. Anything still queued
+    /// once this passes is dropped, so a slow network can't hang Dolphin on exit.
+    pub shutdown_deadline: Duration,
+}
+
+impl Default for GameReporterConfig {
+    fn default() -> Self {
+        Self {
+            report_timeout: None,
+            upload_timeout: None,
+            max_upload_size: DEFAULT_MAX_UPLOAD_SIZE,
+            max_replay_size: DEFAULT_MAX_REPLAY_SIZE,
+            desync_iso_list_path: None,
+            osd: Arc::new(DolphinOsd),
+            abandonment_policy: AbandonmentPolicy::default(),
+            graphql_url: GRAPHQL_URL.to_string(),
+            max_report_attempts: DEFAULT_MAX_REPORT_ATTEMPTS,
+            shutdown_deadline: DEFAULT_SHUTDOWN_DEADLINE,
+        }
+    }
+}
+
+/// Controls whether match abandonment events are reported to the server.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AbandonmentPolicy {
+    /// Report every abandonment.
+    #[default]
+    Always,
+
+    /// Never report abandonments.
+    Never,
+
+    /// Report at most one abandonment per window, dropping any that follow too quickly
+    /// (e.g, repeated quick abandons while someone re-queues).
+    Throttled(Duration),
+}
+
+impl AbandonmentPolicy {
+    /// Returns whether an abandonment at `now` should be reported, given when the last
+    /// reported abandonment was sent (if any).
+    fn allows(&self, last_reported: Option<Instant>, now: Instant) -> bool {
+        match (self, last_reported) {
+            (Self::Always, _) => true,
+            (Self::Never, _) => false,
+            (Self::Throttled(_), None) => true,
+            (Self::Throttled(window), Some(last)) => now.saturating_duration_since(last) >= *window,
+        }
+    }
+}
+
+/// A summary of reporting activity over the current session.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    /// Game reports the server has accepted.
+    pub reports_sent: u32,
+
+    /// Distinct matches that at least one game report was logged for.
+    pub matches_played: u32,
+}
 
 /// Events that we dispatch into the processing thread.
 #[derive(Copy, Clone, Debug)]
 pub(crate) enum ProcessingEvent {
     ReportAvailable,
-    Shutdown,
+
+    /// Send what we can before `deadline`, then stop.
+    Shutdown {
+        deadline: Instant,
+    },
 }
 
 /// Used to pass completion event data to a background processing thread.
@@ -33,7 +168,7 @@ pub(crate) enum CompletionEvent {
         uid: String,
         play_key: String,
         match_id: String,
-        end_mode: u8,
+        end_mode: GameEndMode,
     },
 
     Shutdown,
@@ -56,6 +191,10 @@ pub struct GameReporter {
     completion_thread_notifier: Sender<CompletionEvent>,
     queue: GameReporterQueue,
     replay_data: Arc<Mutex<Vec<u8>>>,
+    spectate_ring: Option<ReplayRing>,
+    abandonment_policy: AbandonmentPolicy,
+    last_abandonment_report: Mutex<Option<Instant>>,
+    session_match_ids: HashSet<String>,
 }
 
 impl GameReporter {
@@ -68,16 +207,28 @@ impl GameReporter {
     /// Currently, failure to spawn any thread should result in a crash - i.e, if we can't
     /// spawn an OS thread, then there are probably far bigger issues at work here.
     pub fn new(api_client: APIClient, user_manager: UserManager, iso_path: String) -> Self {
-        let queue = GameReporterQueue::new(api_client.clone());
+        Self::with_config(api_client, user_manager, iso_path, GameReporterConfig::default())
+    }
+
+    /// Initializes and returns a new `GameReporter` using the provided configuration.
+    ///
+    /// See `GameReporter::new` for details on the background threads this spawns.
+    pub fn with_config(api_client: APIClient, user_manager: UserManager, iso_path: String, config: GameReporterConfig) -> Self {
+        let osd = config.osd.clone();
+        let abandonment_policy = config.abandonment_policy;
+        let graphql_url = config.graphql_url.clone();
+        let desync_iso_list_path = config.desync_iso_list_path.clone();
+        let queue = GameReporterQueue::new(api_client.clone(), config);
 
         // This is a thread-safe "one time" setter that the MD5 hasher thread
         // will set when it's done computing.
         let iso_hash_setter = queue.iso_hash.clone();
+        let iso_hash_progress = queue.iso_hash_progress.clone();
 
         let iso_md5_hasher_thread = thread::Builder::new()
             .name("GameReporterISOHasherThread".into())
             .spawn(move || {
-                iso_md5_hasher::run(iso_hash_setter, iso_path);
+                iso_md5_hasher::run(iso_hash_setter, iso_hash_progress, iso_path, desync_iso_list_path, osd);
             })
             .expect("Failed to spawn GameReporterISOHasherThread.");
 
@@ -96,7 +247,7 @@ impl GameReporter {
         let completion_thread = thread::Builder::new()
             .name("GameReporterCompletionProcessingThread".into())
             .spawn(move || {
-                queue::run_completion(api_client, completion_receiver);
+                queue::run_completion(api_client, graphql_url, completion_receiver);
             })
             .expect("Failed to spawn GameReporterCompletionProcessingThread.");
 
@@ -104,6 +255,10 @@ impl GameReporter {
             user_manager,
             queue,
             replay_data: Arc::new(Mutex::new(Vec::new())),
+            spectate_ring: None,
+            abandonment_policy,
+            last_abandonment_report: Mutex::new(None),
+            session_match_ids: HashSet::new(),
             queue_thread_notifier: queue_sender,
             queue_thread: Some(queue_thread),
             completion_thread_notifier: completion_sender,
@@ -118,10 +273,37 @@ impl GameReporter {
         // that isn't required anymore
     }
 
+    /// Enables (or, with `None`, disables) a rolling spectate buffer that retains only the
+    /// most recent `capacity` bytes of replay data for the current session.
+    ///
+    /// This is kept separately from the full replay buffer, which is always retained for
+    /// uploading, so enabling it has no effect on reporting.
+    pub fn set_spectate_buffer_capacity(&mut self, capacity: Option<usize>) {
+        self.spectate_ring = capacity.map(ReplayRing::new);
+    }
+
+    /// Returns a copy of the replay data currently held in the spectate buffer, oldest first.
+    ///
+    /// This is empty if the spectate buffer is not enabled.
+    pub fn spectate_data(&self) -> Vec<u8> {
+        match &self.spectate_ring {
+            Some(ring) => ring.to_vec(),
+            None => Vec::new(),
+        }
+    }
+
     /// Logs replay data that's passed to it.
     pub fn push_replay_data(&mut self, data: &[u8]) {
         if !data.is_empty() && data[0] == 0x35 {
             self.replay_data = Arc::new(Mutex::new(Vec::new()));
+
+            if let Some(ring) = self.spectate_ring.as_mut() {
+                ring.clear();
+            }
+        }
+
+        if let Some(ring) = self.spectate_ring.as_mut() {
+            ring.push(data);
         }
 
         let mut guard = self.replay_data.lock().unwrap();
@@ -134,7 +316,25 @@ impl GameReporter {
     /// Note that when a new report is added, we transfer ownership of all current replay data
     /// to the game report itself. By doing this, we avoid needing to have a Mutex controlling
     /// access and pushing replay data as it comes in requires no locking.
+    ///
+    /// Reports with malformed player data are logged and dropped here rather than queued, since
+    /// the server would reject them anyway.
     pub fn log_report(&mut self, mut report: GameReport) {
+        if let Err(error) = report.sanitize() {
+            tracing::error!(
+                target: Log::SlippiOnline,
+                ?error,
+                match_id = report.match_id,
+                "Dropping invalid game report"
+            );
+
+            return;
+        }
+
+        if !self.session_match_ids.contains(&report.match_id) {
+            self.session_match_ids.insert(report.match_id.clone());
+        }
+
         report.replay_data = self.replay_data.clone();
         self.queue.add_report(report);
 
@@ -147,15 +347,78 @@ impl GameReporter {
         }
     }
 
-    /// Reports a match abandon event.
+    /// Returns a snapshot of reporting activity for this session.
+    pub fn session_stats(&self) -> SessionStats {
+        SessionStats {
+            reports_sent: self.queue.reports_sent(),
+            matches_played: self.session_match_ids.len() as u32,
+        }
+    }
+
+    /// Pauses or resumes sending reports. Reports logged while paused are held in the queue,
+    /// and sending picks back up from where it left off once resumed.
+    pub fn set_paused(&self, paused: bool) {
+        self.queue.set_paused(paused);
+
+        if !paused {
+            if let Err(e) = self.queue_thread_notifier.send(ProcessingEvent::ReportAvailable) {
+                tracing::error!(
+                    target: Log::SlippiOnline,
+                    error = ?e,
+                    "Unable to dispatch ReportAvailable notification on resume"
+                );
+            }
+        }
+    }
+
+    /// Enables or disables sending anything to the server. This is intended for local
+    /// development: while disabled, reports are still accepted and processed, but their
+    /// payloads are logged at debug level instead of being sent, and replays aren't uploaded.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.queue.set_enabled(enabled);
+    }
+
+    /// Reports a match abandon event, unless the configured `AbandonmentPolicy` says otherwise.
     pub fn report_abandonment(&self, match_id: String) {
+        if !self.queue.is_enabled() {
+            tracing::debug!(target: Log::SlippiOnline, match_id, "Reporting disabled, skipping abandonment");
+            return;
+        }
+
+        {
+            let mut last_report = self
+                .last_abandonment_report
+                .lock()
+                .expect("Unable to lock last abandonment report");
+
+            let now = Instant::now();
+
+            if !self.abandonment_policy.allows(*last_report, now) {
+                tracing::info!(
+                    target: Log::SlippiOnline,
+                    match_id,
+                    policy = ?self.abandonment_policy,
+                    "Suppressing abandonment report"
+                );
+
+                return;
+            }
+
+            *last_report = Some(now);
+        }
+
         let (uid, play_key) = self.user_manager.get(|user| (user.uid.clone(), user.play_key.clone()));
 
         self.queue.report_abandonment(uid, play_key, match_id);
     }
 
     /// Dispatches a completion report to a background processing thread.
-    pub fn report_completion(&self, match_id: String, end_mode: u8) {
+    pub fn report_completion(&self, match_id: String, end_mode: GameEndMode) {
+        if !self.queue.is_enabled() {
+            tracing::debug!(target: Log::SlippiOnline, match_id, ?end_mode, "Reporting disabled, skipping completion");
+            return;
+        }
+
         let (uid, play_key) = self.user_manager.get(|user| (user.uid.clone(), user.play_key.clone()));
 
         let event = CompletionEvent::ReportAvailable {
@@ -185,54 +448,170 @@ impl Deref for GameReporter {
     }
 }
 
+/// Joins `thread`, unless it's still running at `deadline`, in which case this returns `None`
+/// without waiting any longer.
+fn join_until(thread: thread::JoinHandle<()>, deadline: Instant) -> Option<thread::Result<()>> {
+    while !thread.is_finished() {
+        if Instant::now() >= deadline {
+            return None;
+        }
+
+        thread::sleep(SHUTDOWN_POLL_INTERVAL);
+    }
+
+    Some(thread.join())
+}
+
 impl Drop for GameReporter {
-    /// Joins the background threads when we're done, logging if
-    /// any errors are encountered.
+    /// Joins the background threads when we're done, logging if any errors are encountered.
+    ///
+    /// No thread is waited on past the shutdown deadline (plus a short grace period); any
+    /// that are still running by then are left to finish (or not) on their own.
     fn drop(&mut self) {
-        if let Some(queue_thread) = self.queue_thread.take() {
-            if let Err(e) = self.queue_thread_notifier.send(ProcessingEvent::Shutdown) {
+        let deadline = Instant::now() + self.queue.shutdown_deadline();
+        let wait_until = deadline + SHUTDOWN_GRACE_PERIOD;
+
+        // Both threads are notified up front so that they wind down in parallel.
+        if self.queue_thread.is_some() {
+            if let Err(e) = self.queue_thread_notifier.send(ProcessingEvent::Shutdown { deadline }) {
                 tracing::error!(
                     target: Log::SlippiOnline,
                     error = ?e,
-                    "Failed to send shutdown notification to queue processing thread, may hang"
+                    "Failed to send shutdown notification to queue processing thread"
                 );
             }
+        }
 
-            if let Err(e) = queue_thread.join() {
+        if self.completion_thread.is_some() {
+            if let Err(e) = self.completion_thread_notifier.send(CompletionEvent::Shutdown) {
                 tracing::error!(
                     target: Log::SlippiOnline,
                     error = ?e,
-                    "Queue thread failure"
+                    "Failed to send shutdown notification to completion processing thread"
                 );
             }
         }
 
-        if let Some(completion_thread) = self.completion_thread.take() {
-            if let Err(e) = self.completion_thread_notifier.send(CompletionEvent::Shutdown) {
-                tracing::error!(
+        if let Some(queue_thread) = self.queue_thread.take() {
+            match join_until(queue_thread, wait_until) {
+                Some(Ok(())) => {},
+
+                Some(Err(e)) => tracing::error!(
                     target: Log::SlippiOnline,
                     error = ?e,
-                    "Failed to send shutdown notification to completion processing thread, may hang"
-                );
+                    "Queue thread failure"
+                ),
+
+                None => tracing::warn!(
+                    target: Log::SlippiOnline,
+                    unsent = ?self.queue.pending_reports(),
+                    "Queue thread missed the shutdown deadline, no longer waiting on it"
+                ),
             }
+        }
 
-            if let Err(e) = completion_thread.join() {
-                tracing::error!(
+        if let Some(completion_thread) = self.completion_thread.take() {
+            match join_until(completion_thread, wait_until) {
+                Some(Ok(())) => {},
+
+                Some(Err(e)) => tracing::error!(
                     target: Log::SlippiOnline,
                     error = ?e,
                     "Completion thread failure"
-                );
+                ),
+
+                None => tracing::warn!(
+                    target: Log::SlippiOnline,
+                    "Completion thread missed the shutdown deadline, no longer waiting on it"
+                ),
             }
         }
 
         if let Some(iso_md5_hasher_thread) = self.iso_md5_hasher_thread.take() {
-            if let Err(e) = iso_md5_hasher_thread.join() {
-                tracing::error!(
+            match join_until(iso_md5_hasher_thread, wait_until) {
+                Some(Ok(())) => {},
+
+                Some(Err(e)) => tracing::error!(
                     target: Log::SlippiOnline,
                     error = ?e,
                     "ISO MD5 hasher thread failure"
-                );
+                ),
+
+                None => tracing::warn!(
+                    target: Log::SlippiOnline,
+                    progress = self.queue.iso_hash_progress(),
+                    "ISO MD5 hasher is still running at the shutdown deadline, no longer waiting on it"
+                ),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use slippi_gg_api::APIClient;
+
+    use super::*;
+
+    /// Removes a temp folder when dropped, so tests clean up after themselves even on failure.
+    struct TempFolder(PathBuf);
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn game_report(match_id: &str, game_index: u32) -> GameReport {
+        GameReport {
+            match_id: match_id.to_string(),
+            game_index,
+            ..GameReport::test_fixture()
+        }
+    }
+
+    #[test]
+    fn session_stats_count_distinct_matches() {
+        let api_client = APIClient::new("test");
+
+        // Declared first so it's dropped (and removed) last, even if an assertion fails.
+        let folder = TempFolder(std::env::temp_dir().join(format!("slippi-reporter-stats-{}", std::process::id())));
+        let user_manager = UserManager::new(api_client.clone(), folder.0.join("user.json"), "test".to_string());
+
+        let mut reporter = GameReporter::new(api_client, user_manager, "missing.iso".to_string());
+        assert_eq!(reporter.session_stats(), SessionStats::default());
+
+        // Keep anything from actually being sent.
+        reporter.set_paused(true);
+
+        reporter.log_report(game_report("match-a", 1));
+        reporter.log_report(game_report("match-a", 2));
+        reporter.log_report(game_report("match-b", 1));
+
+        assert_eq!(
+            reporter.session_stats(),
+            SessionStats {
+                reports_sent: 0,
+                matches_played: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn abandonment_policy_suppresses_when_disabled() {
+        let now = Instant::now();
+
+        assert!(AbandonmentPolicy::Always.allows(Some(now), now));
+        assert!(!AbandonmentPolicy::Never.allows(None, now));
+    }
+
+    #[test]
+    fn abandonment_policy_throttles_within_window() {
+        let policy = AbandonmentPolicy::Throttled(Duration::from_secs(30));
+        let start = Instant::now();
+
+        assert!(policy.allows(None, start));
+        assert!(!policy.allows(Some(start), start + Duration::from_secs(10)));
+        assert!(policy.allows(Some(start), start + Duration::from_secs(30)));
+    }
+}
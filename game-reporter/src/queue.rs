@@ -2,25 +2,22 @@
 
 use std::collections::VecDeque;
 use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
 use std::sync::mpsc::Receiver;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, TryLockError};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use serde_json::{json, Value};
+use serde_json::json;
 
-use dolphin_integrations::{Color, Dolphin, Duration as OSDDuration, Log};
-use slippi_gg_api::APIClient;
+use dolphin_integrations::{Color, Duration as OSDDuration, Log, OsdSink};
+use slippi_gg_api::redact::{redact, strip_query};
+use slippi_gg_api::{APIClient, GraphQLError, Request};
 
-use crate::types::{GameReport, GameReportRequestPayload, OnlinePlayMode};
-use crate::{CompletionEvent, ProcessingEvent};
-
-const GRAPHQL_URL: &str = "https://gql-gateway-dot-slippi.uc.r.appspot.com/graphql";
-
-/// How many times a report should attempt to send.
-const MAX_REPORT_ATTEMPTS: i32 = 5;
+use crate::types::{GameEndMode, GameReport, GameReportRequestPayload, OnlinePlayMode};
+use crate::{CompletionEvent, GameReporterConfig, ProcessingEvent};
 
 /// Expected response payload when saving a report to the server.
 #[derive(Debug, serde::Deserialize)]
@@ -41,19 +38,89 @@ struct ReportResponse {
 pub struct GameReporterQueue {
     pub api_client: APIClient,
     pub iso_hash: Arc<Mutex<String>>,
+    pub iso_hash_progress: Arc<AtomicU8>,
+    config: GameReporterConfig,
+    paused: Arc<AtomicBool>,
+    enabled: Arc<AtomicBool>,
+    reports_sent: Arc<AtomicU32>,
+    upload_limit: Arc<UploadSizeLimit>,
     inner: Arc<Mutex<VecDeque<GameReport>>>,
 }
 
 impl GameReporterQueue {
     /// Initializes and returns a new game reporter.
-    pub(crate) fn new(api_client: APIClient) -> Self {
+    pub(crate) fn new(api_client: APIClient, config: GameReporterConfig) -> Self {
         Self {
             api_client,
             iso_hash: Arc::new(Mutex::new(String::new())),
+            iso_hash_progress: Arc::new(AtomicU8::new(0)),
+            upload_limit: Arc::new(UploadSizeLimit::new(config.max_upload_size, config.max_replay_size)),
+            config,
+            paused: Arc::new(AtomicBool::new(false)),
+            enabled: Arc::new(AtomicBool::new(true)),
+            reports_sent: Arc::new(AtomicU32::new(0)),
             inner: Arc::new(Mutex::new(VecDeque::new())),
         }
     }
 
+    /// Returns whether report sending is currently paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Returns whether reports are actually sent, as opposed to just logged.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Returns how far along (0-100) the ISO MD5 hash is. Reports can't be sent until it's done.
+    pub fn iso_hash_progress(&self) -> u8 {
+        self.iso_hash_progress.load(Ordering::Relaxed)
+    }
+
+    /// Returns the ISO's MD5 hash, or `None` if it hasn't been computed yet.
+    ///
+    /// This never waits on the lock: if it's briefly held elsewhere, this returns `None`
+    /// rather than blocking the caller.
+    pub fn iso_hash(&self) -> Option<String> {
+        match self.iso_hash.try_lock() {
+            Ok(hash) if !hash.is_empty() => Some(hash.clone()),
+            Ok(_) | Err(TryLockError::WouldBlock) => None,
+
+            Err(TryLockError::Poisoned(error)) => {
+                tracing::error!(target: Log::SlippiOnline, ?error, "Unable to lock iso_hash");
+                None
+            },
+        }
+    }
+
+    /// How long shutdown is allowed to spend sending queued reports.
+    pub(crate) fn shutdown_deadline(&self) -> Duration {
+        self.config.shutdown_deadline
+    }
+
+    /// Returns how many reports are waiting to be sent, or `None` if the queue is busy.
+    pub(crate) fn pending_reports(&self) -> Option<usize> {
+        self.inner.try_lock().ok().map(|queue| queue.len())
+    }
+
+    /// Returns how many reports have been successfully sent since this queue was created.
+    pub fn reports_sent(&self) -> u32 {
+        self.reports_sent.load(Ordering::Relaxed)
+    }
+
+    /// Pauses or resumes report sending. Reports still queue up while paused, and are
+    /// left in place until sending is resumed.
+    pub(crate) fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    /// Enables or disables sending reports. While disabled, reports are still accepted and
+    /// processed, but the payload is logged instead of being sent (and replays aren't uploaded).
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
     /// Adds a new report to the back of the queue.
     ///
     /// (The processing thread pulls from the front)
@@ -81,18 +148,24 @@ impl GameReporterQueue {
             }
         "#;
 
-        let variables = Some(json!({
+        let variables = json!({
             "report": {
                 "matchId": match_id,
                 "fbUid": uid,
                 "playKey": play_key,
             }
-        }));
+        });
 
-        let res = execute_graphql_query(&self.api_client, mutation, variables, Some("abandonOnlineGame"));
+        let res = self
+            .api_client
+            .graphql(mutation)
+            .endpoint(self.config.graphql_url.as_str())
+            .variables(variables)
+            .data_field("abandonOnlineGame")
+            .send::<bool>();
 
         match res {
-            Ok(value) if value == "true" => {
+            Ok(true) => {
                 tracing::info!(target: Log::SlippiOnline, "Successfully executed abandonment request")
             },
             Ok(value) => tracing::error!(target: Log::SlippiOnline, ?value, "Error executing abandonment request",),
@@ -101,7 +174,7 @@ impl GameReporterQueue {
     }
 }
 
-pub(crate) fn run_completion(api_client: APIClient, receiver: Receiver<CompletionEvent>) {
+pub(crate) fn run_completion(api_client: APIClient, graphql_url: String, receiver: Receiver<CompletionEvent>) {
     loop {
         // Watch for notification to do work
         match receiver.recv() {
@@ -111,7 +184,7 @@ pub(crate) fn run_completion(api_client: APIClient, receiver: Receiver<Completio
                 match_id,
                 end_mode,
             }) => {
-                report_completion(&api_client, uid, match_id, play_key, end_mode);
+                report_completion(&api_client, &graphql_url, uid, match_id, play_key, end_mode);
             },
 
             Ok(CompletionEvent::Shutdown) => {
@@ -139,26 +212,38 @@ pub(crate) fn run_completion(api_client: APIClient, receiver: Receiver<Completio
 ///
 /// This doesn't necessarily need to be here, but it's easier to grok the codebase
 /// if we keep all reporting network calls in one module.
-pub fn report_completion(api_client: &APIClient, uid: String, match_id: String, play_key: String, end_mode: u8) {
+pub fn report_completion(
+    api_client: &APIClient,
+    graphql_url: &str,
+    uid: String,
+    match_id: String,
+    play_key: String,
+    end_mode: GameEndMode,
+) {
     let mutation = r#"
         mutation ($report: OnlineGameCompleteInput!) {
             completeOnlineGame (report: $report)
         }
     "#;
 
-    let variables = Some(json!({
+    let variables = json!({
         "report": {
             "matchId": match_id,
             "fbUid": uid,
             "playKey": play_key,
             "endMode": end_mode,
         }
-    }));
+    });
 
-    let res = execute_graphql_query(api_client, mutation, variables, Some("completeOnlineGame"));
+    let res = api_client
+        .graphql(mutation)
+        .endpoint(graphql_url)
+        .variables(variables)
+        .data_field("completeOnlineGame")
+        .send::<bool>();
 
     match res {
-        Ok(value) if value == "true" => {
+        Ok(true) => {
             tracing::info!(target: Log::SlippiOnline, "Successfully executed completion request")
         },
         Ok(value) => tracing::error!(target: Log::SlippiOnline, ?value, "Error executing completion request",),
@@ -175,10 +260,10 @@ pub(crate) fn run(reporter: GameReporterQueue, receiver: Receiver<ProcessingEven
                 process_reports(&reporter, ProcessingEvent::ReportAvailable);
             },
 
-            Ok(ProcessingEvent::Shutdown) => {
+            Ok(event @ ProcessingEvent::Shutdown { .. }) => {
                 tracing::info!(target: Log::SlippiOnline, "Processing thread winding down");
 
-                process_reports(&reporter, ProcessingEvent::Shutdown);
+                process_reports(&reporter, event);
 
                 break;
             },
@@ -201,7 +286,8 @@ pub(crate) fn run(reporter: GameReporterQueue, receiver: Receiver<ProcessingEven
 
 /// Process jobs from the queue.
 fn process_reports(queue: &GameReporterQueue, event: ProcessingEvent) {
-    let Ok(iso_hash) = queue.iso_hash.lock() else {
+    // Copied out so the lock isn't held for the (potentially long) duration of sending.
+    let Ok(iso_hash) = queue.iso_hash.lock().map(|hash| hash.clone()) else {
         tracing::warn!(target: Log::SlippiOnline, "No ISO_HASH available");
         return;
     };
@@ -213,20 +299,69 @@ fn process_reports(queue: &GameReporterQueue, event: ProcessingEvent) {
 
     // Process all reports currently in the queue.
     while !report_queue.is_empty() {
+        // Checked on every pass so that pausing takes effect between reports, not just
+        // between batches. Shutting down while paused leaves the queue as-is rather than
+        // waiting on a resume that may never come.
+        if queue.is_paused() {
+            match event {
+                ProcessingEvent::Shutdown { .. } => tracing::warn!(
+                    target: Log::SlippiOnline,
+                    unsent = report_queue.len(),
+                    "Reporter paused during shutdown, leaving reports unsent"
+                ),
+
+                _ => tracing::info!(target: Log::SlippiOnline, "Reporter paused, holding reports"),
+            }
+
+            break;
+        }
+
+        if let ProcessingEvent::Shutdown { deadline } = event {
+            if Instant::now() >= deadline {
+                tracing::warn!(
+                    target: Log::SlippiOnline,
+                    unsent = report_queue.len(),
+                    "Shutdown deadline passed, dropping unsent reports"
+                );
+
+                break;
+            }
+        }
+
+        if !queue.is_enabled() {
+            if let Some(report) = report_queue.pop_front() {
+                let mut payload = serde_json::to_value(GameReportRequestPayload::with(&report, &iso_hash)).unwrap_or_default();
+                redact(&mut payload);
+
+                tracing::debug!(target: Log::SlippiOnline, %payload, "Reporting disabled, skipping report");
+            }
+
+            continue;
+        }
+
         // We only want to pop if we're successful in sending or if we encounter an error
         // (e.g, max attempts). We pass the locked queue over to work with the borrow checker
         // here, since otherwise we can't pop without some ugly block work to coerce letting
         // a mutable borrow drop.
-        match try_send_next_report(&mut *report_queue, event, &queue.api_client, &iso_hash) {
+        match try_send_next_report(&mut *report_queue, event, &queue.api_client, &iso_hash, &queue.config) {
             Ok(upload_url) => {
                 // Pop the front of the queue. If we have a URL, chuck it all over
                 // to the replay uploader.
                 let report = report_queue.pop_front();
+                queue.reports_sent.fetch_add(1, Ordering::Relaxed);
 
                 tracing::info!(target: Log::SlippiOnline, "Successfully sent report, popping from queue");
 
                 if let (Some(report), Some(upload_url)) = (report, upload_url) {
-                    try_upload_replay_data(report.replay_data, upload_url, &queue.api_client);
+                    try_upload_replay_data(
+                        report.replay_data,
+                        report.online_mode,
+                        upload_url,
+                        &queue.api_client,
+                        timeout_for(event, queue.config.upload_timeout),
+                        &queue.upload_limit,
+                        queue.config.osd.as_ref(),
+                    );
                 }
 
                 thread::sleep(Duration::ZERO)
@@ -247,7 +382,7 @@ fn process_reports(queue: &GameReporterQueue, event: ProcessingEvent) {
                     // Tell player their report failed to send
                     if let Some(report) = report {
                         if report.online_mode == OnlinePlayMode::Ranked {
-                            Dolphin::add_osd_message(
+                            queue.config.osd.add_message(
                                 Color::Red,
                                 OSDDuration::VeryLong,
                                 "Failed to send game report. If you get this often, visit Slippi Discord for help.",
@@ -265,10 +400,8 @@ fn process_reports(queue: &GameReporterQueue, event: ProcessingEvent) {
 /// The true inner error, minus any metadata.
 #[derive(Debug)]
 enum ReportSendErrorKind {
-    Net(slippi_gg_api::Error),
-    JSON(serde_json::Error),
-    GraphQL(String),
-    NotSuccessful(String),
+    GraphQL(GraphQLError),
+    NotSuccessful(ReportResponse),
 }
 
 /// Wraps errors that can occur during report sending.
@@ -288,6 +421,7 @@ fn try_send_next_report(
     event: ProcessingEvent,
     api_client: &APIClient,
     iso_hash: &str,
+    config: &GameReporterConfig,
 ) -> Result<Option<String>, ReportSendError> {
     let report = (*queue).front_mut().expect("Reporter queue is empty yet it shouldn't be");
 
@@ -295,8 +429,8 @@ fn try_send_next_report(
 
     // If we're shutting the thread down, limit max attempts to just 1.
     let max_attempts = match event {
-        ProcessingEvent::Shutdown => 1,
-        _ => MAX_REPORT_ATTEMPTS,
+        ProcessingEvent::Shutdown { .. } => 1,
+        _ => config.max_report_attempts,
     };
 
     let is_last_attempt = report.attempts >= max_attempts;
@@ -317,83 +451,59 @@ fn try_send_next_report(
         }
     "#;
 
-    let variables = Some(json!({
-        "report": payload,
-    }));
+    let mut request = api_client
+        .graphql(mutation)
+        .endpoint(config.graphql_url.as_str())
+        .variables(json!({
+            "report": payload,
+        }))
+        .data_field("reportOnlineGame");
 
-    // Call execute_graphql_query and get the response body as a String.
-    let response_body =
-        execute_graphql_query(api_client, mutation, variables, Some("reportOnlineGame")).map_err(|e| ReportSendError {
-            is_last_attempt,
-            sleep_ms: error_sleep_ms,
-            kind: e,
-        })?;
-
-    // Now, parse the response JSON to get the data you need.
-    let response: ReportResponse = serde_json::from_str(&response_body).map_err(|e| ReportSendError {
-        is_last_attempt,
-        sleep_ms: error_sleep_ms,
-        kind: ReportSendErrorKind::JSON(e),
+    if let Some(timeout) = timeout_for(event, config.report_timeout) {
+        request = request.timeout(timeout);
+    }
+
+    let response: ReportResponse = request.send().map_err(|e| {
+        // Retrying with the same credentials won't get past a 401, so give up right away.
+        let is_unauthorized = matches!(e, GraphQLError::Http { status: 401, .. });
+
+        ReportSendError {
+            is_last_attempt: is_last_attempt || is_unauthorized,
+            sleep_ms: if is_unauthorized { Duration::ZERO } else { error_sleep_ms },
+            kind: ReportSendErrorKind::GraphQL(e),
+        }
     })?;
 
     if !response.success {
         return Err(ReportSendError {
             is_last_attempt,
             sleep_ms: error_sleep_ms,
-            kind: ReportSendErrorKind::NotSuccessful(response_body),
+            kind: ReportSendErrorKind::NotSuccessful(response),
         });
     }
 
     Ok(response.upload_url)
 }
 
-/// Prepares and executes a GraphQL query.
-fn execute_graphql_query(
-    api_client: &APIClient,
-    query: &str,
-    variables: Option<Value>,
-    field: Option<&str>,
-) -> Result<String, ReportSendErrorKind> {
-    // Prepare the GraphQL request payload
-    let request_body = match variables {
-        Some(vars) => json!({
-            "query": query,
-            "variables": vars,
-        }),
-        None => json!({
-            "query": query,
-        }),
-    };
+/// Returns the timeout to use for a request made while handling `event`. During shutdown,
+/// this is cut down so that a single slow request can't run past the shutdown deadline.
+fn timeout_for(event: ProcessingEvent, timeout: Option<Duration>) -> Option<Duration> {
+    match event {
+        ProcessingEvent::Shutdown { deadline } => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)))
+        },
 
-    // Make the GraphQL request
-    let response = api_client
-        .post(GRAPHQL_URL)
-        .send_json(&request_body)
-        .map_err(ReportSendErrorKind::Net)?;
-
-    // Parse the response JSON
-    let response_json: Value =
-        serde_json::from_str(&response.into_string().unwrap_or_default()).map_err(ReportSendErrorKind::JSON)?;
-
-    // Check for GraphQL errors
-    if let Some(errors) = response_json.get("errors") {
-        if errors.is_array() && !errors.as_array().unwrap().is_empty() {
-            let error_message = serde_json::to_string_pretty(errors).unwrap();
-            return Err(ReportSendErrorKind::GraphQL(error_message));
-        }
+        ProcessingEvent::ReportAvailable => timeout,
     }
+}
 
-    // Return the data response
-    if let Some(data) = response_json.get("data") {
-        let result = match field {
-            Some(field) => data.get(field).unwrap_or(data),
-            None => data,
-        };
-        Ok(result.to_string())
-    } else {
-        Err(ReportSendErrorKind::GraphQL(
-            "No 'data' field in the GraphQL response.".to_string(),
-        ))
+/// Applies `timeout` to `request`, if set. Otherwise the request keeps the timeout
+/// configured on the `APIClient` it was created from.
+fn with_timeout(request: Request, timeout: Option<Duration>) -> Request {
+    match timeout {
+        Some(timeout) => request.timeout(timeout),
+        None => request,
     }
 }
 
@@ -425,8 +535,91 @@ fn add_slp_header_and_footer(data: Arc<Mutex<Vec<u8>>>) -> Vec<u8> {
         .collect()
 }
 
+/// The result of checking a replay against an `UploadSizeLimit`.
+#[derive(Debug, PartialEq, Eq)]
+enum UploadSizeCheck {
+    WithinLimit,
+    TooLarge { notify_user: bool },
+}
+
+/// Caps the size of replays we'll try to upload, and tracks whether the user has
+/// already been told about a replay being skipped.
+#[derive(Debug)]
+struct UploadSizeLimit {
+    max_bytes: usize,
+    max_raw_bytes: usize,
+    notified: AtomicBool,
+}
+
+impl UploadSizeLimit {
+    fn new(max_bytes: usize, max_raw_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            max_raw_bytes,
+            notified: AtomicBool::new(false),
+        }
+    }
+
+    /// Checks a compressed `size` against the limit. Only the first oversized replay asks
+    /// for the user to be notified, so that we don't spam them after every game.
+    fn check(&self, size: usize) -> UploadSizeCheck {
+        self.check_against(size, self.max_bytes)
+    }
+
+    /// Checks an uncompressed `size` against the raw limit, before we pay for compression.
+    fn check_raw(&self, size: usize) -> UploadSizeCheck {
+        self.check_against(size, self.max_raw_bytes)
+    }
+
+    fn check_against(&self, size: usize, max_bytes: usize) -> UploadSizeCheck {
+        if size <= max_bytes {
+            return UploadSizeCheck::WithinLimit;
+        }
+
+        UploadSizeCheck::TooLarge {
+            notify_user: !self.notified.swap(true, Ordering::Relaxed),
+        }
+    }
+}
+
+/// Lets the user know that a replay was skipped, so a missing replay isn't a mystery.
+fn notify_replay_too_large(osd: &dyn OsdSink) {
+    osd.add_message(
+        Color::Yellow,
+        OSDDuration::VeryLong,
+        "Replay was too large to upload. Your game was still reported.",
+    );
+}
+
 /// Attempts to compress and upload replay data to the url at `upload_url`.
-fn try_upload_replay_data(data: Arc<Mutex<Vec<u8>>>, upload_url: String, api_client: &APIClient) {
+///
+/// Failures are only surfaced to the player for ranked matches, mirroring how failed reports are handled.
+fn try_upload_replay_data(
+    data: Arc<Mutex<Vec<u8>>>,
+    online_mode: OnlinePlayMode,
+    upload_url: String,
+    api_client: &APIClient,
+    timeout: Option<Duration>,
+    upload_limit: &UploadSizeLimit,
+    osd: &dyn OsdSink,
+) {
+    let raw_size = data.lock().unwrap().len();
+
+    if let UploadSizeCheck::TooLarge { notify_user } = upload_limit.check_raw(raw_size) {
+        tracing::warn!(
+            target: Log::SlippiOnline,
+            raw_size,
+            max_raw_size = upload_limit.max_raw_bytes,
+            "Replay is too large to compress and upload, skipping"
+        );
+
+        if notify_user {
+            notify_replay_too_large(osd);
+        }
+
+        return;
+    }
+
     let contents = add_slp_header_and_footer(data);
 
     let mut gzipped_data = vec![0u8; contents.len()]; // Resize to some initial size
@@ -436,20 +629,362 @@ fn try_upload_replay_data(data: Arc<Mutex<Vec<u8>>>, upload_url: String, api_cli
 
         Err(error) => {
             tracing::error!(target: Log::SlippiOnline, ?error, "Failed to compress replay");
+
+            if online_mode == OnlinePlayMode::Ranked {
+                osd.add_message(
+                    Color::Red,
+                    OSDDuration::VeryLong,
+                    "Failed to prepare replay for upload. Your game was still reported.",
+                );
+            }
+
             return;
         },
     };
 
     gzipped_data.resize(res_size, 0);
 
-    let response = api_client
-        .put(upload_url.as_str())
+    if let UploadSizeCheck::TooLarge { notify_user } = upload_limit.check(res_size) {
+        tracing::warn!(
+            target: Log::SlippiOnline,
+            size = res_size,
+            raw_size = contents.len(),
+            max_size = upload_limit.max_bytes,
+            "Replay is too large to upload, skipping"
+        );
+
+        if notify_user {
+            notify_replay_too_large(osd);
+        }
+
+        return;
+    }
+
+    // GCS enforces this range itself, so it has to agree with the limit we checked above.
+    let content_length_range = format!("0,{}", upload_limit.max_bytes);
+
+    let response = with_timeout(api_client.put(upload_url.as_str()), timeout)
         .set("Content-Type", "application/octet-stream")
         .set("Content-Encoding", "gzip")
-        .set("X-Goog-Content-Length-Range", "0,10000000")
+        .set("X-Goog-Content-Length-Range", &content_length_range)
         .send_bytes(&gzipped_data);
 
     if let Err(error) = response {
-        tracing::error!(target: Log::SlippiOnline, ?error, "Failed to upload replay data",);
+        // The upload URL is signed, so log the error without it. A status means the storage
+        // server got the replay and refused it (e.g, an expired URL), which the player should
+        // be able to tell apart from their connection dropping.
+        let (error, message) = match error {
+            slippi_gg_api::Error::Status(status, _) => (
+                format!("Status {status}"),
+                "Replay upload was rejected by the server. Your game was still reported.",
+            ),
+
+            slippi_gg_api::Error::Transport(transport) => (
+                format!("{}: {}", transport.kind(), transport.message().unwrap_or_default()),
+                "Failed to upload replay due to a network error. Your game was still reported.",
+            ),
+        };
+
+        tracing::error!(
+            target: Log::SlippiOnline,
+            error,
+            url = strip_query(&upload_url),
+            "Failed to upload replay data"
+        );
+
+        if online_mode == OnlinePlayMode::Ranked {
+            osd.add_message(Color::Red, OSDDuration::VeryLong, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    use dolphin_integrations::RecordingOsd;
+
+    use super::*;
+
+    #[test]
+    fn iso_hash_is_only_returned_once_available() {
+        let queue = GameReporterQueue::new(APIClient::new("test"), GameReporterConfig::default());
+        assert_eq!(queue.iso_hash(), None);
+
+        *queue.iso_hash.lock().unwrap() = "hash".to_string();
+        assert_eq!(queue.iso_hash().as_deref(), Some("hash"));
+
+        // Doesn't wait on whoever holds the lock.
+        let _lock = queue.iso_hash.lock().unwrap();
+        assert_eq!(queue.iso_hash(), None);
+    }
+
+    #[test]
+    fn paused_queue_holds_reports() {
+        let queue = GameReporterQueue::new(APIClient::new("test"), GameReporterConfig::default());
+        queue.set_paused(true);
+
+        queue.add_report(GameReport::test_fixture());
+        queue.add_report(GameReport::test_fixture());
+
+        process_reports(&queue, ProcessingEvent::ReportAvailable);
+        assert_eq!(queue.inner.lock().unwrap().len(), 2);
+        assert_eq!(queue.inner.lock().unwrap()[0].attempts, 0);
+
+        // Shutdown shouldn't hang or send anything while paused either.
+        let deadline = Instant::now() + Duration::from_secs(1);
+        process_reports(&queue, ProcessingEvent::Shutdown { deadline });
+        assert_eq!(queue.inner.lock().unwrap().len(), 2);
+        assert_eq!(queue.inner.lock().unwrap()[0].attempts, 0);
+    }
+
+    #[test]
+    fn shutdown_respects_deadline() {
+        // Accepts connections but never responds, so every send hangs until it times out.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let config = GameReporterConfig {
+            graphql_url: format!("http://{}/graphql", listener.local_addr().unwrap()),
+            report_timeout: Some(Duration::from_secs(30)),
+            osd: Arc::new(RecordingOsd::default()),
+            ..GameReporterConfig::default()
+        };
+
+        let queue = GameReporterQueue::new(APIClient::new("test"), config);
+        *queue.iso_hash.lock().unwrap() = "hash".to_string();
+        queue.add_report(GameReport::test_fixture());
+        queue.add_report(GameReport::test_fixture());
+
+        let started = Instant::now();
+        process_reports(
+            &queue,
+            ProcessingEvent::Shutdown {
+                deadline: started + Duration::from_millis(200),
+            },
+        );
+
+        // The first report's send is cut off at the deadline, and the second is never tried.
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(queue.pending_reports(), Some(1));
+        assert_eq!(queue.inner.lock().unwrap()[0].attempts, 0);
+
+        drop(listener);
+    }
+
+    #[test]
+    fn disabled_queue_skips_sending() {
+        // Nothing is listening here, so an actual send attempt would fail and stay queued.
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let config = GameReporterConfig {
+            graphql_url: format!("http://{closed}/graphql"),
+            ..GameReporterConfig::default()
+        };
+
+        let queue = GameReporterQueue::new(APIClient::new("test"), config);
+        queue.set_enabled(false);
+
+        queue.add_report(GameReport::test_fixture());
+        queue.add_report(GameReport::test_fixture());
+
+        process_reports(&queue, ProcessingEvent::ReportAvailable);
+        assert!(queue.inner.lock().unwrap().is_empty());
+        assert_eq!(queue.reports_sent(), 0);
+    }
+
+    #[test]
+    fn upload_size_limit_notifies_once() {
+        let limit = UploadSizeLimit::new(100, usize::MAX);
+
+        assert_eq!(limit.check(100), UploadSizeCheck::WithinLimit);
+        assert_eq!(limit.check(101), UploadSizeCheck::TooLarge { notify_user: true });
+        assert_eq!(limit.check(5000), UploadSizeCheck::TooLarge { notify_user: false });
+        assert_eq!(limit.check(10), UploadSizeCheck::WithinLimit);
+    }
+
+    #[test]
+    fn oversized_replays_are_not_uploaded() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
+
+        let limit = UploadSizeLimit::new(1, usize::MAX);
+        let osd = RecordingOsd::default();
+
+        for _ in 0..2 {
+            let data = Arc::new(Mutex::new(vec![0u8; 4096]));
+            try_upload_replay_data(
+                data,
+                OnlinePlayMode::Ranked,
+                url.clone(),
+                &APIClient::new("test"),
+                None,
+                &limit,
+                &osd,
+            );
+        }
+
+        // Nothing should have tried to connect, and the user is only told once.
+        assert!(listener.accept().is_err());
+        assert_eq!(osd.messages().len(), 1);
+        assert_eq!(osd.messages()[0].0, Color::Yellow);
+    }
+
+    #[test]
+    fn reports_use_configured_endpoint_and_attempts() {
+        let api_client = APIClient::new("test");
+
+        let ok = "{\"data\":{\"reportOnlineGame\":{\"success\":true,\"uploadUrl\":null}}}";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{ok}",
+            ok.len()
+        );
+
+        let config = GameReporterConfig {
+            graphql_url: serve_once(response),
+            ..GameReporterConfig::default()
+        };
+
+        let mut reports = VecDeque::from([GameReport::test_fixture()]);
+        let result = try_send_next_report(&mut reports, ProcessingEvent::ReportAvailable, &api_client, "hash", &config);
+        assert!(matches!(result, Ok(None)));
+
+        // A single allowed attempt means the first failure is also the last.
+        let config = GameReporterConfig {
+            graphql_url: serve_once("HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n"),
+            max_report_attempts: 1,
+            ..GameReporterConfig::default()
+        };
+
+        let mut reports = VecDeque::from([GameReport::test_fixture()]);
+        let error =
+            try_send_next_report(&mut reports, ProcessingEvent::ReportAvailable, &api_client, "hash", &config).unwrap_err();
+        assert!(error.is_last_attempt);
+    }
+
+    #[test]
+    fn unauthorized_reports_are_not_retried() {
+        let config = GameReporterConfig {
+            graphql_url: serve_once("HTTP/1.1 401 Unauthorized\r\nContent-Length: 13\r\n\r\nBad play key."),
+            ..GameReporterConfig::default()
+        };
+
+        let mut reports = VecDeque::from([GameReport::test_fixture()]);
+        let error = try_send_next_report(
+            &mut reports,
+            ProcessingEvent::ReportAvailable,
+            &APIClient::new("test"),
+            "hash",
+            &config,
+        )
+        .unwrap_err();
+
+        assert!(error.is_last_attempt);
+        assert_eq!(error.sleep_ms, Duration::ZERO);
+        assert!(matches!(
+            error.kind,
+            ReportSendErrorKind::GraphQL(GraphQLError::Http { status: 401, ref body }) if body == "Bad play key."
+        ));
+    }
+
+    #[test]
+    fn oversized_raw_replays_are_not_compressed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
+
+        // The compressed limit alone would let this through, since zeroes compress well.
+        let limit = UploadSizeLimit::new(usize::MAX, 1024);
+        let osd = RecordingOsd::default();
+
+        let data = Arc::new(Mutex::new(vec![0u8; 4096]));
+        try_upload_replay_data(data, OnlinePlayMode::Ranked, url, &APIClient::new("test"), None, &limit, &osd);
+
+        assert!(listener.accept().is_err());
+        assert_eq!(osd.messages().len(), 1);
+    }
+
+    #[test]
+    fn upload_failures_are_only_shown_for_ranked() {
+        let limit = UploadSizeLimit::new(usize::MAX, usize::MAX);
+        let failure = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n";
+
+        for (mode, expected) in [(OnlinePlayMode::Ranked, 1), (OnlinePlayMode::Unranked, 0)] {
+            let osd = RecordingOsd::default();
+            let data = Arc::new(Mutex::new(vec![0u8; 64]));
+
+            try_upload_replay_data(data, mode, serve_once(failure), &APIClient::new("test"), None, &limit, &osd);
+
+            assert_eq!(osd.messages().len(), expected);
+        }
+    }
+
+    #[test]
+    fn upload_failures_distinguish_rejections_from_network_errors() {
+        let limit = UploadSizeLimit::new(usize::MAX, usize::MAX);
+        let data = || Arc::new(Mutex::new(vec![0u8; 64]));
+
+        let osd = RecordingOsd::default();
+        let rejected = serve_once("HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n");
+        try_upload_replay_data(
+            data(),
+            OnlinePlayMode::Ranked,
+            rejected,
+            &APIClient::new("test"),
+            None,
+            &limit,
+            &osd,
+        );
+        assert!(osd.messages()[0].2.contains("rejected by the server"));
+
+        // Nothing listening here anymore, so the connection is refused.
+        let osd = RecordingOsd::default();
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let url = format!("http://{closed}/upload");
+        try_upload_replay_data(
+            data(),
+            OnlinePlayMode::Ranked,
+            url,
+            &APIClient::new("test"),
+            None,
+            &limit,
+            &osd,
+        );
+        assert!(osd.messages()[0].2.contains("network error"));
+    }
+
+    /// Serves a single canned HTTP response on a local port, returning the URL to hit.
+    fn serve_once(response: impl Into<String>) -> String {
+        use std::io::Read;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+        let response: String = response.into();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        url
+    }
+
+    #[test]
+    fn request_timeout_overrides_client_timeout() {
+        // Accepts connections but never responds, so only a timeout gets us out.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+
+        let api_client = APIClient::new("test");
+        let started = Instant::now();
+        let result = with_timeout(api_client.post(&url), Some(Duration::from_millis(200))).call();
+
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        drop(listener);
     }
 }
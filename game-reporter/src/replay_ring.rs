@@ -0,0 +1,76 @@
+//! A small fixed-capacity byte buffer used to keep a rolling window of replay
+//! data around for spectating, independent of the full buffer used for uploads.
+
+use std::collections::VecDeque;
+
+/// Retains only the most recent `capacity` bytes that have been pushed into it.
+#[derive(Debug)]
+pub(crate) struct ReplayRing {
+    capacity: usize,
+    data: VecDeque<u8>,
+}
+
+impl ReplayRing {
+    /// Creates a new, empty ring that will hold at most `capacity` bytes.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            data: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Appends `bytes`, evicting the oldest data as needed to stay within capacity.
+    pub(crate) fn push(&mut self, bytes: &[u8]) {
+        // Anything beyond the last `capacity` bytes would just be evicted immediately.
+        let bytes = &bytes[bytes.len().saturating_sub(self.capacity)..];
+
+        let overflow = (self.data.len() + bytes.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(bytes);
+    }
+
+    /// Drops everything currently held.
+    pub(crate) fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Returns a copy of the currently held bytes, oldest first.
+    pub(crate) fn to_vec(&self) -> Vec<u8> {
+        self.data.iter().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_everything_under_capacity() {
+        let mut ring = ReplayRing::new(8);
+        ring.push(&[1, 2, 3]);
+        ring.push(&[4, 5]);
+        assert_eq!(ring.to_vec(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn evicts_oldest_bytes_first() {
+        let mut ring = ReplayRing::new(4);
+        ring.push(&[1, 2, 3]);
+        ring.push(&[4, 5, 6]);
+        assert_eq!(ring.to_vec(), vec![3, 4, 5, 6]);
+
+        ring.push(&[7]);
+        assert_eq!(ring.to_vec(), vec![4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn oversized_pushes_keep_only_the_tail() {
+        let mut ring = ReplayRing::new(3);
+        ring.push(&[1]);
+        ring.push(&[2, 3, 4, 5, 6]);
+        assert_eq!(ring.to_vec(), vec![4, 5, 6]);
+
+        ring.clear();
+        assert!(ring.to_vec().is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+//! Helpers for deriving a set score from the reports logged over the course of a match.
+
+use std::collections::BTreeMap;
+
+use crate::types::GameReport;
+
+/// Computes the `(p1_wins, p2_wins)` score for `match_id` from the provided game reports.
+///
+/// Reports for other matches are ignored, as are games without a winner for either side
+/// (ties, disconnects and other incomplete games report a negative `winner_index`). If the
+/// same game shows up more than once, the last report for it is the one that counts.
+pub fn set_score<'a, I>(match_id: &str, reports: I) -> (u32, u32)
+where
+    I: IntoIterator<Item = &'a GameReport>,
+{
+    let winners: BTreeMap<(u32, u32), i8> = reports
+        .into_iter()
+        .filter(|report| report.match_id == match_id)
+        .map(|report| ((report.game_index, report.tie_break_index), report.winner_index))
+        .collect();
+
+    winners.values().fold((0, 0), |(p1, p2), winner| match winner {
+        0 => (p1 + 1, p2),
+        1 => (p1, p2 + 1),
+        _ => (p1, p2),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(match_id: &str, game_index: u32, winner_index: i8) -> GameReport {
+        GameReport {
+            match_id: match_id.to_string(),
+            game_index,
+            winner_index,
+            ..GameReport::test_fixture()
+        }
+    }
+
+    #[test]
+    fn scores_a_two_one_set() {
+        let reports = vec![game("match", 1, 0), game("match", 2, 1), game("match", 3, 0)];
+        assert_eq!(set_score("match", &reports), (2, 1));
+    }
+
+    #[test]
+    fn ignores_incomplete_games_and_other_matches() {
+        let mut tie = game("match", 2, -1);
+        tie.tie_break_index = 1;
+
+        let reports = vec![game("match", 1, 1), tie, game("other", 1, 0)];
+        assert_eq!(set_score("match", &reports), (0, 1));
+        assert_eq!(set_score("missing", &reports), (0, 0));
+    }
+
+    #[test]
+    fn counts_resent_games_once() {
+        let reports = vec![game("match", 1, 0), game("match", 1, 0)];
+        assert_eq!(set_score("match", &reports), (1, 0));
+    }
+}
@@ -14,6 +14,57 @@ pub enum OnlinePlayMode {
     Teams = 3,
 }
 
+/// Returned when a byte doesn't correspond to a known `OnlinePlayMode`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownOnlinePlayMode(pub u8);
+
+impl TryFrom<u8> for OnlinePlayMode {
+    type Error = UnknownOnlinePlayMode;
+
+    /// Parses the mode byte as it's sent over from the game/EXI side.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Ranked),
+            1 => Ok(Self::Unranked),
+            2 => Ok(Self::Direct),
+            3 => Ok(Self::Teams),
+            _ => Err(UnknownOnlinePlayMode(value)),
+        }
+    }
+}
+
+/// How a match ended, as sent over from the game/EXI side when reporting completion. The
+/// values match Melee's game end method (what reports send as `gameEndMethod`).
+#[derive(Copy, Clone, Debug, serde_repr::Serialize_repr, PartialEq, Eq)]
+#[repr(u8)]
+pub enum GameEndMode {
+    Unresolved = 0,
+    Time = 1,
+    Game = 2,
+    Resolved = 3,
+    NoContest = 7,
+}
+
+/// Returned when a byte doesn't correspond to a known `GameEndMode`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownGameEndMode(pub u8);
+
+impl TryFrom<u8> for GameEndMode {
+    type Error = UnknownGameEndMode;
+
+    /// Parses the end mode byte as it's sent over from the game/EXI side.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Unresolved),
+            1 => Ok(Self::Time),
+            2 => Ok(Self::Game),
+            3 => Ok(Self::Resolved),
+            7 => Ok(Self::NoContest),
+            _ => Err(UnknownGameEndMode(value)),
+        }
+    }
+}
+
 /// Describes metadata about a game that we need to log to the server.
 #[derive(Debug)]
 pub struct GameReport {
@@ -36,6 +87,36 @@ pub struct GameReport {
     pub replay_data: Arc<Mutex<Vec<u8>>>,
 }
 
+/// The most players a single game can have (i.e, the number of controller ports).
+const MAX_PLAYERS: usize = 4;
+
+/// Reasons a `GameReport` may be rejected before it's queued for sending.
+#[derive(Debug, PartialEq)]
+pub enum InvalidGameReport {
+    TooManyPlayers(usize),
+    Player { index: usize, error: InvalidPlayerReport },
+}
+
+impl GameReport {
+    /// Runs `PlayerReport::sanitize` over every player on this report, returning the first
+    /// failure encountered (along with the index of the offending player).
+    ///
+    /// Valid reports are left untouched.
+    pub fn sanitize(&mut self) -> Result<(), InvalidGameReport> {
+        if self.players.len() > MAX_PLAYERS {
+            return Err(InvalidGameReport::TooManyPlayers(self.players.len()));
+        }
+
+        for (index, player) in self.players.iter_mut().enumerate() {
+            player
+                .sanitize()
+                .map_err(|error| InvalidGameReport::Player { index, error })?;
+        }
+
+        Ok(())
+    }
+}
+
 /// Player metadata payload that's logged with game info.
 #[derive(Debug, serde::Serialize)]
 pub struct PlayerReport {
@@ -64,6 +145,49 @@ pub struct PlayerReport {
     pub starting_percent: i64,
 }
 
+/// Reasons a `PlayerReport` may be rejected before it's queued for sending.
+#[derive(Debug, PartialEq)]
+pub enum InvalidPlayerReport {
+    MissingUid,
+    NonFiniteDamage(f64),
+    NegativeStartingStocks(i64),
+    StocksExceedStarting { remaining: u8, starting: i64 },
+}
+
+impl PlayerReport {
+    /// Checks the player data that came over from the C++ side before it gets queued.
+    ///
+    /// Values that are out of range but still recoverable (e.g, negative damage or percent)
+    /// are clamped in place. Anything that can't be sensibly reported returns an error, as the
+    /// server would just reject it and we'd burn retries on it.
+    pub fn sanitize(&mut self) -> Result<(), InvalidPlayerReport> {
+        if self.uid.is_empty() {
+            return Err(InvalidPlayerReport::MissingUid);
+        }
+
+        if !self.damage_done.is_finite() {
+            return Err(InvalidPlayerReport::NonFiniteDamage(self.damage_done));
+        }
+
+        if self.starting_stocks < 0 {
+            return Err(InvalidPlayerReport::NegativeStartingStocks(self.starting_stocks));
+        }
+
+        // Timed matches report zero starting stocks, so only check this when it's meaningful.
+        if self.starting_stocks > 0 && i64::from(self.stocks_remaining) > self.starting_stocks {
+            return Err(InvalidPlayerReport::StocksExceedStarting {
+                remaining: self.stocks_remaining,
+                starting: self.starting_stocks,
+            });
+        }
+
+        self.damage_done = self.damage_done.max(0.0);
+        self.starting_percent = self.starting_percent.max(0);
+
+        Ok(())
+    }
+}
+
 /// The core report payload that's posted to the server.
 #[derive(Debug, serde::Serialize)]
 pub struct GameReportRequestPayload<'a> {
@@ -103,6 +227,30 @@ pub struct GameReportRequestPayload<'a> {
     pub stage_id: i32,
 }
 
+#[cfg(test)]
+impl GameReport {
+    /// A ranked report for game 1 of `match`, won by the first player, with no players or
+    /// replay data. Tests override whichever fields they care about.
+    pub(crate) fn test_fixture() -> Self {
+        Self {
+            uid: "uid".to_string(),
+            play_key: "play_key".to_string(),
+            online_mode: OnlinePlayMode::Ranked,
+            match_id: "match".to_string(),
+            attempts: 0,
+            duration_frames: 0,
+            game_index: 1,
+            tie_break_index: 0,
+            winner_index: 0,
+            game_end_method: 2,
+            lras_initiator: -1,
+            stage_id: 31,
+            players: Vec::new(),
+            replay_data: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
 impl<'a> GameReportRequestPayload<'a> {
     /// Builds a report request payload that can be serialized for POSTing
     /// to the server.
@@ -124,3 +272,169 @@ impl<'a> GameReportRequestPayload<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player_report(uid: &str) -> PlayerReport {
+        PlayerReport {
+            uid: uid.to_string(),
+            slot_type: 0,
+            damage_done: 120.5,
+            stocks_remaining: 2,
+            character_id: 2,
+            color_id: 0,
+            starting_stocks: 4,
+            starting_percent: 0,
+        }
+    }
+
+    fn game_report(players: Vec<PlayerReport>) -> GameReport {
+        GameReport {
+            players,
+            ..GameReport::test_fixture()
+        }
+    }
+
+    #[test]
+    fn request_payload_matches_server_schema() {
+        let mut p2 = player_report("p2");
+        p2.slot_type = 1;
+        p2.damage_done = 88.0;
+        p2.stocks_remaining = 0;
+        p2.character_id = 20;
+        p2.color_id = 3;
+
+        let report = game_report(vec![player_report("p1"), p2]);
+        let payload = GameReportRequestPayload::with(&report, "iso-md5");
+
+        let expected = serde_json::json!({
+            "fbUid": "uid",
+            "mode": 0,
+            "players": [
+                {
+                    "fbUid": "p1",
+                    "slotType": 0,
+                    "damageDone": 120.5,
+                    "stocksRemaining": 2,
+                    "characterId": 2,
+                    "colorId": 0,
+                    "startingStocks": 4,
+                    "startingPercent": 0,
+                },
+                {
+                    "fbUid": "p2",
+                    "slotType": 1,
+                    "damageDone": 88.0,
+                    "stocksRemaining": 0,
+                    "characterId": 20,
+                    "colorId": 3,
+                    "startingStocks": 4,
+                    "startingPercent": 0,
+                },
+            ],
+            "isoHash": "iso-md5",
+            "matchId": "match",
+            "playKey": "play_key",
+            "gameDurationFrames": 0,
+            "gameIndex": 1,
+            "tiebreakIndex": 0,
+            "winnerIdx": 0,
+            "gameEndMethod": 2,
+            "lrasInitiator": -1,
+            "stageId": 31,
+        });
+
+        assert_eq!(serde_json::to_value(&payload).unwrap(), expected);
+    }
+
+    #[test]
+    fn parses_known_online_play_mode_bytes() {
+        assert_eq!(OnlinePlayMode::try_from(0), Ok(OnlinePlayMode::Ranked));
+        assert_eq!(OnlinePlayMode::try_from(1), Ok(OnlinePlayMode::Unranked));
+        assert_eq!(OnlinePlayMode::try_from(2), Ok(OnlinePlayMode::Direct));
+        assert_eq!(OnlinePlayMode::try_from(3), Ok(OnlinePlayMode::Teams));
+    }
+
+    #[test]
+    fn rejects_unknown_online_play_mode_bytes() {
+        assert_eq!(OnlinePlayMode::try_from(4), Err(UnknownOnlinePlayMode(4)));
+        assert_eq!(OnlinePlayMode::try_from(0xFF), Err(UnknownOnlinePlayMode(0xFF)));
+    }
+
+    #[test]
+    fn parses_known_game_end_mode_bytes() {
+        assert_eq!(GameEndMode::try_from(0), Ok(GameEndMode::Unresolved));
+        assert_eq!(GameEndMode::try_from(1), Ok(GameEndMode::Time));
+        assert_eq!(GameEndMode::try_from(2), Ok(GameEndMode::Game));
+        assert_eq!(GameEndMode::try_from(3), Ok(GameEndMode::Resolved));
+        assert_eq!(GameEndMode::try_from(7), Ok(GameEndMode::NoContest));
+
+        // The server still receives the raw byte.
+        assert_eq!(serde_json::to_value(GameEndMode::NoContest).unwrap(), 7);
+    }
+
+    #[test]
+    fn rejects_unknown_game_end_mode_bytes() {
+        assert_eq!(GameEndMode::try_from(4), Err(UnknownGameEndMode(4)));
+        assert_eq!(GameEndMode::try_from(0xFF), Err(UnknownGameEndMode(0xFF)));
+    }
+
+    #[test]
+    fn leaves_valid_players_untouched() {
+        let mut report = game_report(vec![player_report("p1"), player_report("p2")]);
+        assert_eq!(report.sanitize(), Ok(()));
+        assert_eq!(report.players[0].damage_done, 120.5);
+        assert_eq!(report.players[1].stocks_remaining, 2);
+    }
+
+    #[test]
+    fn clamps_negative_damage_and_percent() {
+        let mut p = player_report("p1");
+        p.damage_done = -3.0;
+        p.starting_percent = -10;
+        assert_eq!(p.sanitize(), Ok(()));
+        assert_eq!(p.damage_done, 0.0);
+        assert_eq!(p.starting_percent, 0);
+    }
+
+    #[test]
+    fn rejects_invalid_player_fields() {
+        let mut p = player_report("");
+        assert_eq!(p.sanitize(), Err(InvalidPlayerReport::MissingUid));
+
+        let mut p = player_report("p1");
+        p.damage_done = f64::NAN;
+        assert!(matches!(p.sanitize(), Err(InvalidPlayerReport::NonFiniteDamage(_))));
+
+        let mut p = player_report("p1");
+        p.starting_stocks = -1;
+        assert_eq!(p.sanitize(), Err(InvalidPlayerReport::NegativeStartingStocks(-1)));
+
+        let mut p = player_report("p1");
+        p.stocks_remaining = 5;
+        assert_eq!(
+            p.sanitize(),
+            Err(InvalidPlayerReport::StocksExceedStarting {
+                remaining: 5,
+                starting: 4
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_reports_with_invalid_players() {
+        let mut report = game_report(vec![player_report("p1"), player_report("")]);
+        assert_eq!(
+            report.sanitize(),
+            Err(InvalidGameReport::Player {
+                index: 1,
+                error: InvalidPlayerReport::MissingUid
+            })
+        );
+
+        let mut report = game_report((0..5).map(|_| player_report("p")).collect());
+        assert_eq!(report.sanitize(), Err(InvalidGameReport::TooManyPlayers(5)));
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use rodio::Sink;
+
+/// An in-progress crossfade from the previously playing song to a new one.
+///
+/// The outgoing song keeps playing on its own sink until the fade completes; dropping
+/// this stops it immediately.
+pub(crate) struct Crossfade {
+    outgoing: Sink,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Crossfade {
+    pub(crate) fn new(outgoing: Sink, duration: Duration, now: Instant) -> Self {
+        Self {
+            outgoing,
+            started: now,
+            duration,
+        }
+    }
+
+    /// Moves both sinks to where they should be at `now` for a target `volume`, returning
+    /// `true` once the fade has finished and this can be dropped.
+    pub(crate) fn tick(&self, incoming: &Sink, volume: f32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.started);
+        let (outgoing_volume, incoming_volume) = volumes(volume, elapsed, self.duration);
+
+        self.outgoing.set_volume(outgoing_volume);
+        incoming.set_volume(incoming_volume);
+
+        elapsed >= self.duration
+    }
+}
+
+/// Returns the `(outgoing, incoming)` volumes `elapsed` into a crossfade lasting `duration`.
+fn volumes(volume: f32, elapsed: Duration, duration: Duration) -> (f32, f32) {
+    let progress = match duration.is_zero() {
+        true => 1.0,
+        false => (elapsed.as_secs_f32() / duration.as_secs_f32()).clamp(0.0, 1.0),
+    };
+
+    (volume * (1.0 - progress), volume * progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramps_linearly_between_songs() {
+        let duration = Duration::from_secs(2);
+
+        assert_eq!(volumes(0.8, Duration::ZERO, duration), (0.8, 0.0));
+        assert_eq!(volumes(0.8, Duration::from_secs(1), duration), (0.4, 0.4));
+        assert_eq!(volumes(0.8, Duration::from_secs(2), duration), (0.0, 0.8));
+    }
+
+    #[test]
+    fn clamps_past_the_end_of_the_fade() {
+        assert_eq!(volumes(1.0, Duration::from_secs(5), Duration::from_secs(1)), (0.0, 1.0));
+        assert_eq!(volumes(1.0, Duration::ZERO, Duration::ZERO), (0.0, 1.0));
+    }
+}
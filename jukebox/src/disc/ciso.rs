@@ -17,14 +17,12 @@ pub(crate) fn get_ciso_header(iso: &mut File) -> Result<Option<CisoHeader>> {
         IsoKind::Ciso => {
             // Get the block size
             let mut block_size = [0; 0x4];
-            iso.seek(std::io::SeekFrom::Start(0x4)).map_err(IsoSeek)?;
-            iso.read_exact(&mut block_size).map_err(IsoRead)?;
+            read_header_bytes(iso, 0x4, &mut block_size)?;
             let block_size = u32::from_le_bytes(block_size);
 
             // Get the block map
             let mut block_map = [0; CISO_BLOCK_MAP_SIZE];
-            iso.seek(std::io::SeekFrom::Start(0x8)).map_err(IsoSeek)?;
-            iso.read_exact(&mut block_map).map_err(IsoRead)?;
+            read_header_bytes(iso, 0x8, &mut block_map)?;
 
             Ok(Some((block_size, block_map)))
         },
@@ -32,6 +30,18 @@ pub(crate) fn get_ciso_header(iso: &mut File) -> Result<Option<CisoHeader>> {
     }
 }
 
+/// Fills `buf` with the bytes in `iso` at `offset`
+fn read_header_bytes(iso: &mut File, offset: u64, buf: &mut [u8]) -> Result<()> {
+    iso.seek(std::io::SeekFrom::Start(offset))
+        .map_err(|source| IsoSeek { offset, source })?;
+
+    iso.read_exact(buf).map_err(|source| IsoRead {
+        offset,
+        length: buf.len(),
+        source,
+    })
+}
+
 // Given an offset for an standard disc image, return the offset for a ciso
 // image
 pub(crate) fn get_ciso_offset(header: &CisoHeader, offset: u64) -> Option<u64> {
@@ -6,31 +6,56 @@ use crate::Result;
 
 mod ciso;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum IsoKind {
     Standard,
     Ciso,
+    /// An NKit image. These still look like a standard disc, but have had data
+    /// removed, so offsets into them can't be trusted.
+    Nkit,
+    /// A Dolphin GCZ compressed image.
+    Gcz,
     Unknown,
 }
 
+/// How much of the start of the file we need to look at to classify it. The NKit
+/// magic word sits at 0x200, past the standard disc header.
+const ISO_KIND_HEADER_LEN: usize = 0x204;
+
 /// Given an iso file, determine what kind it is
 pub(crate) fn get_iso_kind(iso: &mut File) -> Result<IsoKind> {
-    // Get the first four bytes
-    iso.rewind().map_err(IsoSeek)?;
-    let mut initial_bytes = [0; 4];
-    iso.read_exact(&mut initial_bytes).map_err(IsoRead)?;
+    iso.rewind().map_err(|source| IsoSeek { offset: 0, source })?;
+
+    let mut header = Vec::with_capacity(ISO_KIND_HEADER_LEN);
+    iso.by_ref()
+        .take(ISO_KIND_HEADER_LEN as u64)
+        .read_to_end(&mut header)
+        .map_err(|source| IsoRead {
+            offset: 0,
+            length: ISO_KIND_HEADER_LEN,
+            source,
+        })?;
+
+    Ok(classify_iso_header(&header))
+}
 
-    // Get the four bytes at 0x1c
-    iso.seek(std::io::SeekFrom::Start(0x1c)).map_err(IsoSeek)?;
-    let mut dvd_magic_bytes = [0; 4];
-    iso.read_exact(&mut dvd_magic_bytes).map_err(IsoRead)?;
+/// Determines the kind of disc image from the first `ISO_KIND_HEADER_LEN` bytes of it.
+/// Anything too short to tell is `Unknown`.
+fn classify_iso_header(header: &[u8]) -> IsoKind {
+    let initial_bytes = header.get(0..4);
+    let dvd_magic_bytes = header.get(0x1c..0x20);
+    let nkit_magic_bytes = header.get(0x200..0x204);
 
-    match (initial_bytes, dvd_magic_bytes) {
+    match (initial_bytes, dvd_magic_bytes, nkit_magic_bytes) {
+        // NKit images keep the DVD magic word, so need to be checked first
+        (_, Some([0xc2, 0x33, 0x9F, 0x3D]), Some(b"NKIT")) => IsoKind::Nkit,
         // DVD Magic Word
-        (_, [0xc2, 0x33, 0x9F, 0x3D]) => Ok(IsoKind::Standard),
+        (_, Some([0xc2, 0x33, 0x9F, 0x3D]), _) => IsoKind::Standard,
         // CISO header
-        ([0x43, 0x49, 0x53, 0x4F], _) => Ok(IsoKind::Ciso),
-        _ => Ok(IsoKind::Unknown),
+        (Some([0x43, 0x49, 0x53, 0x4F]), _, _) => IsoKind::Ciso,
+        // GCZ magic (0xB10BC001, little endian)
+        (Some([0x01, 0xC0, 0x0B, 0xB1]), _, _) => IsoKind::Gcz,
+        _ => IsoKind::Unknown,
     }
 }
 
@@ -59,3 +84,38 @@ pub(crate) fn create_offset_locator_fn(iso: &mut File) -> Result<impl Fn(u64) ->
         None => Some(offset),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a header with `initial` at the start, and the DVD magic word at 0x1c if `dvd_magic` is set.
+    fn header(initial: &[u8], dvd_magic: bool) -> Vec<u8> {
+        let mut header = vec![0; 0x20];
+        header[..initial.len()].copy_from_slice(initial);
+
+        if dvd_magic {
+            header[0x1c..0x20].copy_from_slice(&[0xc2, 0x33, 0x9F, 0x3D]);
+        }
+
+        header
+    }
+
+    #[test]
+    fn classifies_container_headers() {
+        assert_eq!(classify_iso_header(&header(b"GALE01", true)), IsoKind::Standard);
+        assert_eq!(classify_iso_header(&header(b"CISO", false)), IsoKind::Ciso);
+        assert_eq!(classify_iso_header(&header(&[0x01, 0xC0, 0x0B, 0xB1], false)), IsoKind::Gcz);
+        assert_eq!(classify_iso_header(&header(b"RVZ\x01", false)), IsoKind::Unknown);
+        assert_eq!(classify_iso_header(&[0x43, 0x49]), IsoKind::Unknown);
+    }
+
+    #[test]
+    fn classifies_nkit_images() {
+        let mut nkit = header(b"GALE01", true);
+        nkit.resize(ISO_KIND_HEADER_LEN, 0);
+        nkit[0x200..0x204].copy_from_slice(b"NKIT");
+
+        assert_eq!(classify_iso_header(&nkit), IsoKind::Nkit);
+    }
+}
@@ -22,11 +22,22 @@ pub enum JukeboxError {
     #[error("Unable to play sound with rodio: {0}")]
     AudioPlayback(#[from] rodio::PlayError),
 
-    #[error("Failed to seek the ISO: {0}")]
-    IsoSeek(std::io::Error),
-
-    #[error("Failed to read the ISO: {0}")]
-    IsoRead(std::io::Error),
+    #[error("Failed to seek the ISO to 0x{offset:x}: {source}")]
+    IsoSeek { offset: u64, source: std::io::Error },
+
+    #[error("Failed to read {length} bytes from the ISO at 0x{offset:x}: {source}")]
+    IsoRead {
+        offset: u64,
+        length: usize,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to load song (hps offset: 0x{hps_offset:x}, length: {hps_length}): {source}")]
+    SongLoad {
+        hps_offset: u64,
+        hps_length: usize,
+        source: Box<JukeboxError>,
+    },
 
     #[error("The provided game file is not supported")]
     UnsupportedIso,
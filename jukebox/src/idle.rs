@@ -0,0 +1,78 @@
+use std::time::{Duration, Instant};
+
+/// Tracks how long the jukebox has gone without playing anything, so that the
+/// audio device can be released once it's been idle for long enough.
+#[derive(Debug)]
+pub(crate) struct IdleTimer {
+    timeout: Duration,
+    idle_since: Option<Instant>,
+}
+
+impl IdleTimer {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            idle_since: None,
+        }
+    }
+
+    /// Records whether the player is currently idle as of `now`, returning `true`
+    /// once it has been continuously idle for at least the timeout.
+    pub(crate) fn is_expired(&mut self, is_idle: bool, now: Instant) -> bool {
+        if !is_idle {
+            self.idle_since = None;
+            return false;
+        }
+
+        let idle_since = *self.idle_since.get_or_insert(now);
+        now.duration_since(idle_since) >= self.timeout
+    }
+
+    /// Starts the idle period over, e.g after the audio device has been released.
+    pub(crate) fn reset(&mut self) {
+        self.idle_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_after_continuous_idle_period() {
+        let start = Instant::now();
+        let mut timer = IdleTimer::new(Duration::from_secs(30));
+
+        assert!(!timer.is_expired(true, start));
+        assert!(!timer.is_expired(true, start + Duration::from_secs(29)));
+        assert!(timer.is_expired(true, start + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn activity_restarts_the_idle_period() {
+        let start = Instant::now();
+        let mut timer = IdleTimer::new(Duration::from_secs(30));
+
+        assert!(!timer.is_expired(true, start));
+        assert!(!timer.is_expired(false, start + Duration::from_secs(20)));
+        assert!(!timer.is_expired(true, start + Duration::from_secs(40)));
+        assert!(timer.is_expired(true, start + Duration::from_secs(70)));
+    }
+
+    #[test]
+    fn release_and_reacquire_cycle() {
+        let start = Instant::now();
+        let mut timer = IdleTimer::new(Duration::from_secs(30));
+
+        // Idle long enough to release the device...
+        assert!(!timer.is_expired(true, start));
+        assert!(timer.is_expired(true, start + Duration::from_secs(30)));
+        timer.reset();
+
+        // ...then a song re-acquires it and plays for a while before stopping again.
+        assert!(!timer.is_expired(false, start + Duration::from_secs(60)));
+        assert!(!timer.is_expired(true, start + Duration::from_secs(90)));
+        assert!(!timer.is_expired(true, start + Duration::from_secs(119)));
+        assert!(timer.is_expired(true, start + Duration::from_secs(120)));
+    }
+}
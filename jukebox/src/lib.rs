@@ -1,11 +1,13 @@
 use std::convert::TryInto;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::fs::File;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::mpsc::{channel, Receiver, RecvError, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use dolphin_integrations::{Color, Dolphin, Duration as OSDDuration, Log};
+use dolphin_integrations::{Color, DolphinOsd, Duration as OSDDuration, Log, OsdSink};
 use hps_decode::Hps;
-use rodio::{OutputStream, Sink};
+use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
 
 use crate::Message::*;
 
@@ -13,24 +15,68 @@ mod errors;
 pub use errors::JukeboxError;
 use JukeboxError::*;
 
+mod crossfade;
+use crossfade::Crossfade;
+
 mod disc;
 use disc::{get_iso_kind, IsoKind};
 
+mod idle;
+use idle::IdleTimer;
+
 mod utils;
 use utils::copy_bytes_from_file;
 
+mod volume;
+use volume::Volumes;
+
 pub(crate) type Result<T> = std::result::Result<T, JukeboxError>;
 
-/// By default Slippi Jukebox plays music slightly louder than vanilla melee
-/// does. This reduces the overall music volume output to 80%. Not totally sure
-/// if that's the correct amount, but it sounds about right.
-const VOLUME_REDUCTION_MULTIPLIER: f32 = 0.8;
+/// How often the player thread wakes up to check whether it's been idle long
+/// enough to release the audio device. Only used when an idle timeout is set.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often volumes are updated while crossfading between songs.
+const CROSSFADE_TICK: Duration = Duration::from_millis(20);
+
+/// Optional behavior for a `Jukebox`. The `Default` implementation matches
+/// how the jukebox has always behaved.
+#[derive(Clone, Debug)]
+pub struct JukeboxOptions {
+    /// If set, the audio output device is released once no music has played for
+    /// this long, and lazily re-acquired on the next `StartSong`. This frees up the
+    /// device while sitting in menus, which matters for exclusive-mode devices.
+    pub idle_timeout: Option<Duration>,
+
+    /// How long a newly started song takes to ramp up from silence to the current
+    /// volume. A zero duration starts songs at full volume immediately.
+    pub fade_in: Duration,
+
+    /// Where user-facing messages (e.g, an unsupported ISO) get sent.
+    pub osd: Arc<dyn OsdSink>,
+}
+
+impl Default for JukeboxOptions {
+    fn default() -> Self {
+        Self {
+            idle_timeout: None,
+            fade_in: Duration::ZERO,
+            osd: Arc::new(DolphinOsd),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum Message {
     StartSong(u64, usize),
     StopMusic,
+    Pause,
+    Resume,
     SetVolume(VolumeControl, u8),
+    Mute,
+    Unmute,
+    ReinitAudio,
+    SetCrossfade(Duration),
     JukeboxDropped,
 }
 
@@ -41,24 +87,104 @@ pub enum VolumeControl {
     DolphinMusic,
 }
 
+/// The song the jukebox is currently playing, identified by where its hps file lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NowPlaying {
+    pub hps_offset: u64,
+    pub hps_length: usize,
+}
+
+/// There's no track list to resolve stage names from, so songs are described by where their
+/// hps file lives.
+impl fmt::Display for NowPlaying {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hps offset: 0x{:x}, length: {}", self.hps_offset, self.hps_length)
+    }
+}
+
 #[derive(Debug)]
 pub struct Jukebox {
     tx: Sender<Message>,
+    now_playing: Arc<Mutex<Option<NowPlaying>>>,
+}
+
+/// Holds the audio device handles. The stream needs to stay alive for as long
+/// as the sink is in use.
+struct AudioOutput {
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    sink: Sink,
+}
+
+impl AudioOutput {
+    /// Returns the held audio output, (re-)acquiring the default audio device
+    /// at `volume` if it was previously released.
+    fn get_or_acquire(output: &mut Option<AudioOutput>, volume: f32) -> Result<&mut AudioOutput> {
+        let audio = match output.take() {
+            Some(audio) => audio,
+
+            None => {
+                let (stream, handle) = OutputStream::try_default()?;
+                let sink = Sink::try_new(&handle)?;
+                sink.set_volume(volume);
+
+                AudioOutput {
+                    _stream: stream,
+                    handle,
+                    sink,
+                }
+            },
+        };
+
+        Ok(output.insert(audio))
+    }
+}
+
+/// Reads back what the player thread is playing.
+fn get_now_playing(now_playing: &Mutex<Option<NowPlaying>>) -> Option<NowPlaying> {
+    match now_playing.lock() {
+        Ok(lock) => *lock,
+
+        Err(error) => {
+            tracing::error!(target: Log::Jukebox, ?error, "Unable to lock now playing state");
+            None
+        },
+    }
+}
+
+/// Records what the player thread is playing, for `Jukebox::now_playing`.
+fn set_now_playing(now_playing: &Mutex<Option<NowPlaying>>, song: Option<NowPlaying>) {
+    match now_playing.lock() {
+        Ok(mut lock) => *lock = song,
+        Err(error) => tracing::error!(target: Log::Jukebox, ?error, "Unable to lock now playing state"),
+    }
 }
 
 impl Jukebox {
     /// Returns an instance of Slippi Jukebox. Playback can be controlled by
     /// calling the instance's public methods.
-    pub fn new(iso_path: String, initial_dolphin_system_volume: u8, initial_dolphin_music_volume: u8) -> Result<Self> {
+    pub fn new(
+        iso_path: String,
+        initial_dolphin_system_volume: u8,
+        initial_dolphin_music_volume: u8,
+        options: JukeboxOptions,
+    ) -> Result<Self> {
         tracing::info!(target: Log::Jukebox, "Initializing Slippi Jukebox");
 
         // Make sure the provided ISO is supported
-        if let IsoKind::Unknown = get_iso_kind(&mut File::open(&iso_path)?)? {
-            Dolphin::add_osd_message(
-                Color::Red,
-                OSDDuration::VeryLong,
-                "\nYour ISO is not supported by Slippi Jukebox. Music will not play.",
-            );
+        let unsupported_reason = match get_iso_kind(&mut File::open(&iso_path)?)? {
+            IsoKind::Standard | IsoKind::Ciso => None,
+            IsoKind::Nkit => Some(
+                "\nNKit ISOs are not supported by Slippi Jukebox. Music will not play.\nConvert it back to a full ISO to use Jukebox.",
+            ),
+            IsoKind::Gcz => Some(
+                "\nCompressed (GCZ) ISOs are not supported by Slippi Jukebox. Music will not play.\nDecompress it to an ISO to use Jukebox.",
+            ),
+            IsoKind::Unknown => Some("\nYour ISO is not supported by Slippi Jukebox. Music will not play."),
+        };
+
+        if let Some(reason) = unsupported_reason {
+            options.osd.add_message(Color::Red, OSDDuration::VeryLong, reason);
             return Err(UnsupportedIso);
         }
 
@@ -66,11 +192,22 @@ impl Jukebox {
         // SlippiJukebox player thread
         let (tx, rx) = channel::<Message>();
 
+        // Updated by the player thread as songs start and stop.
+        let now_playing = Arc::new(Mutex::new(None));
+        let player_now_playing = now_playing.clone();
+
         // Spawn the thread that will handle loading music and playing it back
         std::thread::Builder::new()
             .name("SlippiJukebox".to_string())
             .spawn(move || {
-                if let Err(e) = Self::start(rx, iso_path, initial_dolphin_system_volume, initial_dolphin_music_volume) {
+                if let Err(e) = Self::start(
+                    rx,
+                    iso_path,
+                    initial_dolphin_system_volume,
+                    initial_dolphin_music_volume,
+                    options,
+                    player_now_playing,
+                ) {
                     tracing::error!(
                         target: Log::Jukebox,
                         error = ?e,
@@ -80,7 +217,7 @@ impl Jukebox {
             })
             .map_err(ThreadSpawn)?;
 
-        Ok(Self { tx })
+        Ok(Self { tx, now_playing })
     }
 
     /// This can be thought of as jukebox's "main" function.
@@ -91,24 +228,130 @@ impl Jukebox {
         iso_path: String,
         initial_dolphin_system_volume: u8,
         initial_dolphin_music_volume: u8,
+        options: JukeboxOptions,
+        now_playing: Arc<Mutex<Option<NowPlaying>>>,
     ) -> Result<()> {
-        let (_stream, stream_handle) = OutputStream::try_default()?;
-        let sink = Sink::try_new(&stream_handle)?;
+        let mut volumes = Volumes::new(initial_dolphin_system_volume, initial_dolphin_music_volume);
+
+        let mut output = None;
+        AudioOutput::get_or_acquire(&mut output, volumes.sink_volume())?;
+
+        let mut idle_timer = options.idle_timeout.map(IdleTimer::new);
+
+        let mut crossfade_duration = Duration::ZERO;
+        let mut crossfade: Option<Crossfade> = None;
 
         let mut iso = File::open(&iso_path)?;
         let get_real_offset = disc::create_offset_locator_fn(&mut iso)?;
 
-        let mut melee_music_volume = 1.0;
-        let mut dolphin_system_volume = (initial_dolphin_system_volume as f32 / 100.0).clamp(0.0, 1.0);
-        let mut dolphin_music_volume = (initial_dolphin_music_volume as f32 / 100.0).clamp(0.0, 1.0);
-
-        sink.set_volume(melee_music_volume * dolphin_system_volume * dolphin_music_volume * VOLUME_REDUCTION_MULTIPLIER);
+        // Set when a message handler needs another message processed straight away,
+        // e.g to restart the current song on a fresh audio device.
+        let mut follow_up: Option<Message> = None;
 
         loop {
-            match rx.recv()? {
+            // Wake up periodically if there's a crossfade to drive, or if we might need to
+            // release the audio device.
+            let poll_interval = match (&crossfade, &output, &idle_timer) {
+                (Some(_), _, _) => Some(CROSSFADE_TICK),
+                (None, Some(_), Some(_)) => Some(IDLE_POLL_INTERVAL),
+                _ => None,
+            };
+
+            let message = match (follow_up.take(), poll_interval) {
+                (Some(message), _) => Some(message),
+
+                (None, Some(interval)) => match rx.recv_timeout(interval) {
+                    Ok(message) => Some(message),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return Err(ChannelSenderDisconnected(RecvError)),
+                },
+
+                (None, None) => Some(rx.recv()?),
+            };
+
+            let crossfade_finished = match (&crossfade, &output) {
+                (Some(fade), Some(audio)) => fade.tick(&audio.sink, volumes.sink_volume(), Instant::now()),
+                _ => false,
+            };
+
+            if crossfade_finished {
+                crossfade = None;
+            }
+
+            if let (Some(timer), Some(audio)) = (idle_timer.as_mut(), output.as_ref()) {
+                if timer.is_expired(audio.sink.empty(), Instant::now()) {
+                    tracing::info!(target: Log::Jukebox, "Releasing audio device after idle timeout");
+                    timer.reset();
+                    crossfade = None;
+                    output = None;
+                }
+            }
+
+            let Some(message) = message else {
+                continue;
+            };
+
+            match message {
                 StartSong(hps_offset, hps_length) => {
-                    // Stop the currently playing song
-                    sink.stop();
+                    // Whatever was playing is on its way out, and the new song might fail to load.
+                    set_now_playing(&now_playing, None);
+
+                    let volume = volumes.sink_volume();
+
+                    let audio_output = match AudioOutput::get_or_acquire(&mut output, volume) {
+                        Ok(audio) => audio,
+                        Err(e) => {
+                            tracing::error!(target: Log::Jukebox, error = ?e, "Failed to acquire audio device. Cannot play song.");
+                            continue;
+                        },
+                    };
+
+                    // Only one song fades out at a time, so cut off anything still fading.
+                    crossfade = None;
+
+                    let mut device_lost = false;
+
+                    if crossfade_duration.is_zero() || audio_output.sink.empty() {
+                        // Stop the currently playing song
+                        audio_output.sink.stop();
+                        audio_output.sink.set_volume(volume);
+                    } else {
+                        // Leave the current song playing on its own sink and bring the new
+                        // one in underneath it. The loop ticks the volumes from here.
+                        match Sink::try_new(&audio_output.handle) {
+                            Ok(incoming) => {
+                                incoming.set_volume(0.0);
+                                let outgoing = std::mem::replace(&mut audio_output.sink, incoming);
+                                crossfade = Some(Crossfade::new(outgoing, crossfade_duration, Instant::now()));
+                            },
+
+                            Err(e) => {
+                                tracing::warn!(target: Log::Jukebox, error = ?e, "Failed to create crossfade sink, audio device may be gone");
+                                device_lost = true;
+                            },
+                        }
+                    }
+
+                    // Failing to create a sink is the symptom of a dead stream (e.g, unplugged
+                    // headphones), so start over on whatever the default device is now.
+                    let audio_output = match device_lost {
+                        true => {
+                            tracing::info!(target: Log::Jukebox, "Re-initializing audio device");
+                            output = None;
+
+                            match AudioOutput::get_or_acquire(&mut output, volume) {
+                                Ok(audio) => audio,
+                                Err(e) => {
+                                    tracing::error!(target: Log::Jukebox, error = ?e, "Failed to re-acquire audio device. Cannot play song.");
+                                    continue;
+                                },
+                            }
+                        },
+
+                        false => audio_output,
+                    };
+
+                    let sink = &audio_output.sink;
 
                     // Get the _real_ offset of the hps file on the iso
                     let real_hps_offset = match get_real_offset(hps_offset) {
@@ -123,10 +366,22 @@ impl Jukebox {
                     };
 
                     // Parse the bytes as an Hps
-                    let hps: Hps = match copy_bytes_from_file(&mut iso, real_hps_offset, hps_length)?.try_into() {
+                    let bytes = copy_bytes_from_file(&mut iso, real_hps_offset, hps_length).map_err(|e| SongLoad {
+                        hps_offset,
+                        hps_length,
+                        source: Box::new(e),
+                    })?;
+
+                    let hps: Hps = match bytes.try_into() {
                         Ok(hps) => hps,
                         Err(e) => {
-                            tracing::error!(target: Log::Jukebox, error = ?e, "Failed to parse bytes into an Hps. Cannot play song.");
+                            tracing::error!(
+                                target: Log::Jukebox,
+                                error = ?e,
+                                hps_offset,
+                                hps_length,
+                                "Failed to parse bytes into an Hps. Cannot play song."
+                            );
                             continue;
                         },
                     };
@@ -135,8 +390,14 @@ impl Jukebox {
                     let audio = match hps.decode() {
                         Ok(audio) => audio,
                         Err(e) => {
-                            tracing::error!(target: Log::Jukebox, error = ?e, "Failed to decode hps into audio. Cannot play song.");
-                            Dolphin::add_osd_message(
+                            tracing::error!(
+                                target: Log::Jukebox,
+                                error = ?e,
+                                hps_offset,
+                                hps_length,
+                                "Failed to decode hps into audio. Cannot play song."
+                            );
+                            options.osd.add_message(
                                 Color::Red,
                                 OSDDuration::Normal,
                                 "Invalid music data found in ISO. This music will not play.",
@@ -145,24 +406,68 @@ impl Jukebox {
                         },
                     };
 
-                    // Play the song
-                    sink.append(audio);
+                    // Play the song. Stopping mid-fade just drops the source, so there's
+                    // nothing extra to cancel here.
+                    if options.fade_in.is_zero() {
+                        sink.append(audio);
+                    } else {
+                        sink.append(audio.fade_in(options.fade_in));
+                    }
+
                     sink.play();
-                },
-                SetVolume(control, volume) => {
-                    use VolumeControl::*;
 
-                    match control {
-                        Melee => melee_music_volume = (volume as f32 / 254.0).clamp(0.0, 1.0),
-                        DolphinSystem => dolphin_system_volume = (volume as f32 / 100.0).clamp(0.0, 1.0),
-                        DolphinMusic => dolphin_music_volume = (volume as f32 / 100.0).clamp(0.0, 1.0),
+                    set_now_playing(&now_playing, Some(NowPlaying { hps_offset, hps_length }));
+                },
+                SetVolume(..) | Mute | Unmute => {
+                    // Mid-crossfade, the next tick picks up the new volume instead.
+                    let sink = match (&output, &crossfade) {
+                        (Some(audio), None) => Some(&audio.sink),
+                        _ => None,
                     };
 
-                    sink.set_volume(
-                        melee_music_volume * dolphin_system_volume * dolphin_music_volume * VOLUME_REDUCTION_MULTIPLIER,
-                    );
+                    volumes.apply(message, sink);
+                },
+                StopMusic => {
+                    crossfade = None;
+                    set_now_playing(&now_playing, None);
+
+                    if let Some(audio) = &output {
+                        audio.sink.stop();
+                        audio.sink.set_volume(volumes.sink_volume());
+                    }
+                },
+                Pause => {
+                    // Finish any crossfade early so there's only one song to resume.
+                    crossfade = None;
+
+                    if let Some(audio) = &output {
+                        audio.sink.pause();
+                        audio.sink.set_volume(volumes.sink_volume());
+                    }
                 },
-                StopMusic => sink.stop(),
+                Resume => {
+                    // Nothing loaded means nothing to resume.
+                    if let Some(audio) = output.as_ref().filter(|audio| !audio.sink.empty()) {
+                        audio.sink.play();
+                    }
+                },
+                ReinitAudio => {
+                    tracing::info!(target: Log::Jukebox, "Re-initializing audio device");
+
+                    // Drop the old device entirely, since it may well be gone.
+                    crossfade = None;
+                    output = None;
+
+                    if let Err(e) = AudioOutput::get_or_acquire(&mut output, volumes.sink_volume()) {
+                        tracing::error!(target: Log::Jukebox, error = ?e, "Failed to re-acquire audio device");
+                        continue;
+                    }
+
+                    if let Some(NowPlaying { hps_offset, hps_length }) = get_now_playing(&now_playing) {
+                        follow_up = Some(StartSong(hps_offset, hps_length));
+                    }
+                },
+                SetCrossfade(duration) => crossfade_duration = duration,
                 JukeboxDropped => return Ok(()),
             }
         }
@@ -185,6 +490,49 @@ impl Jukebox {
         let _ = self.tx.send(StopMusic);
     }
 
+    /// Returns the song that's currently playing, if any. Paused songs still count as playing.
+    pub fn now_playing(&self) -> Option<NowPlaying> {
+        get_now_playing(&self.now_playing)
+    }
+
+    /// Pauses any currently playing music in place
+    pub fn pause(&mut self) {
+        tracing::info!(target: Log::Jukebox, "Pause music");
+        let _ = self.tx.send(Pause);
+    }
+
+    /// Resumes paused music from where it left off. Does nothing if no song is loaded
+    pub fn resume(&mut self) {
+        tracing::info!(target: Log::Jukebox, "Resume music");
+        let _ = self.tx.send(Resume);
+    }
+
+    /// Silences the jukebox without touching any of the volume controls
+    pub fn mute(&mut self) {
+        tracing::info!(target: Log::Jukebox, "Mute music");
+        let _ = self.tx.send(Mute);
+    }
+
+    /// Restores the volume from before `mute`, including any volume changes made while muted
+    pub fn unmute(&mut self) {
+        tracing::info!(target: Log::Jukebox, "Unmute music");
+        let _ = self.tx.send(Unmute);
+    }
+
+    /// Drops the current audio device and re-acquires the default one, restarting the
+    /// current song on it. Call this after the system's audio device changes
+    pub fn reinit_audio(&mut self) {
+        tracing::info!(target: Log::Jukebox, "Reinit audio");
+        let _ = self.tx.send(ReinitAudio);
+    }
+
+    /// Sets how long to crossfade between songs when a new one starts while another is
+    /// still playing. A zero duration (the default) cuts straight to the new song.
+    pub fn set_crossfade(&mut self, duration: Duration) {
+        tracing::info!(target: Log::Jukebox, "Set crossfade: {duration:?}");
+        let _ = self.tx.send(SetCrossfade(duration));
+    }
+
     // Update the volume for any of Jukebox's volume controls
     pub fn set_volume(&mut self, volume_control: VolumeControl, volume: u8) {
         tracing::info!(target: Log::Jukebox, "Change {volume_control:?} volume: {volume}");
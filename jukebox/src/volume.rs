@@ -0,0 +1,110 @@
+use rodio::Sink;
+
+use crate::{Message, VolumeControl};
+
+/// By default Slippi Jukebox plays music slightly louder than vanilla melee
+/// does. This reduces the overall music volume output to 80%. Not totally sure
+/// if that's the correct amount, but it sounds about right.
+const VOLUME_REDUCTION_MULTIPLIER: f32 = 0.8;
+
+/// The individual volume controls that make up the sink volume, plus whether
+/// the jukebox is muted. Muting leaves the controls alone, so unmuting always
+/// restores whatever they add up to at that point.
+#[derive(Debug)]
+pub(crate) struct Volumes {
+    melee_music: f32,
+    dolphin_system: f32,
+    dolphin_music: f32,
+    muted: bool,
+}
+
+impl Volumes {
+    pub(crate) fn new(dolphin_system_volume: u8, dolphin_music_volume: u8) -> Self {
+        Self {
+            melee_music: 1.0,
+            dolphin_system: (dolphin_system_volume as f32 / 100.0).clamp(0.0, 1.0),
+            dolphin_music: (dolphin_music_volume as f32 / 100.0).clamp(0.0, 1.0),
+            muted: false,
+        }
+    }
+
+    /// Updates a single volume control. Melee's volume ranges from 0-254, while
+    /// Dolphin's range from 0-100.
+    pub(crate) fn set(&mut self, control: VolumeControl, volume: u8) {
+        match control {
+            VolumeControl::Melee => self.melee_music = (volume as f32 / 254.0).clamp(0.0, 1.0),
+            VolumeControl::DolphinSystem => self.dolphin_system = (volume as f32 / 100.0).clamp(0.0, 1.0),
+            VolumeControl::DolphinMusic => self.dolphin_music = (volume as f32 / 100.0).clamp(0.0, 1.0),
+        }
+    }
+
+    pub(crate) fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Applies a `SetVolume`, `Mute` or `Unmute` message, then brings `sink` (if any) in line
+    /// with the new volume. Any other message is ignored.
+    pub(crate) fn apply(&mut self, message: Message, sink: Option<&Sink>) {
+        match message {
+            Message::SetVolume(control, volume) => self.set(control, volume),
+            Message::Mute => self.set_muted(true),
+            Message::Unmute => self.set_muted(false),
+            _ => return,
+        }
+
+        if let Some(sink) = sink {
+            sink.set_volume(self.sink_volume());
+        }
+    }
+
+    /// Computes the sink volume from the individual volume controls.
+    pub(crate) fn sink_volume(&self) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+
+        self.melee_music * self.dolphin_system * self.dolphin_music * VOLUME_REDUCTION_MULTIPLIER
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unmute_restores_the_current_volume_product() {
+        let mut volumes = Volumes::new(100, 50);
+        assert_eq!(volumes.sink_volume(), 0.5 * VOLUME_REDUCTION_MULTIPLIER);
+
+        volumes.set_muted(true);
+        assert_eq!(volumes.sink_volume(), 0.0);
+
+        // Changes while muted stay silent, but are picked up on unmute.
+        volumes.set(VolumeControl::DolphinMusic, 25);
+        volumes.set(VolumeControl::Melee, 127);
+        assert_eq!(volumes.sink_volume(), 0.0);
+
+        volumes.set_muted(false);
+        assert_eq!(volumes.sink_volume(), 0.5 * 0.25 * VOLUME_REDUCTION_MULTIPLIER);
+    }
+
+    #[test]
+    fn mute_message_sequence_leaves_the_sink_at_the_recomputed_volume() {
+        let (sink, _queue) = Sink::new_idle();
+        let mut volumes = Volumes::new(100, 50);
+
+        volumes.apply(Message::Mute, Some(&sink));
+        assert_eq!(sink.volume(), 0.0);
+
+        volumes.apply(Message::SetVolume(VolumeControl::DolphinMusic, 25), Some(&sink));
+        volumes.apply(Message::SetVolume(VolumeControl::Melee, 127), Some(&sink));
+        assert_eq!(sink.volume(), 0.0);
+
+        volumes.apply(Message::Unmute, Some(&sink));
+        assert_eq!(sink.volume(), 0.5 * 0.25 * VOLUME_REDUCTION_MULTIPLIER);
+
+        // Other messages leave the volume alone.
+        volumes.apply(Message::StopMusic, Some(&sink));
+        assert_eq!(sink.volume(), 0.5 * 0.25 * VOLUME_REDUCTION_MULTIPLIER);
+    }
+}
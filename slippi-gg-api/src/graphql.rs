@@ -0,0 +1,390 @@
+//! A small builder for GraphQL requests against the slippi.gg API, so that callers
+//! don't each need to hand-roll request bodies and error extraction.
+
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::redact::redact;
+use crate::{APIClient, Error};
+
+/// The default GraphQL endpoint.
+pub const GRAPHQL_URL: &str = "https://gql-gateway-dot-slippi.uc.r.appspot.com/graphql";
+
+/// Errors that can occur when executing a GraphQL request.
+#[derive(Debug, thiserror::Error)]
+pub enum GraphQLError {
+    /// The request failed at the transport level (e.g, DNS, a refused connection, or a timeout).
+    #[error("GraphQL request failed: {0}")]
+    Request(Box<Error>),
+
+    /// The server responded with a non-2xx status. A 401 usually means the user's
+    /// credentials are no longer valid, whereas a 5xx is likely transient.
+    #[error("GraphQL request failed with status {status}: {body}")]
+    Http { status: u16, body: String },
+
+    #[error("Unable to read GraphQL response body: {0}")]
+    IO(#[from] std::io::Error),
+
+    /// The response wasn't valid JSON, or didn't match the expected shape.
+    #[error("Invalid GraphQL response: {0}")]
+    InvalidResponse(#[from] serde_json::Error),
+
+    /// The server responded, but reported errors for the query.
+    #[error("GraphQL server returned errors: {0}")]
+    Server(String),
+
+    /// The variables passed to `GraphQLBuilder::variables` couldn't be serialized to JSON.
+    #[error("Unable to serialize GraphQL variables: {0}")]
+    InvalidVariables(serde_json::Error),
+
+    #[error("GraphQL response is missing the `{0}` field")]
+    MissingField(String),
+}
+
+/// Builds and sends a GraphQL request. Create one via `APIClient::graphql`.
+#[derive(Debug)]
+pub struct GraphQLBuilder {
+    client: APIClient,
+    endpoint: String,
+    query: String,
+    variables: Option<Result<Value, serde_json::Error>>,
+    data_field: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl GraphQLBuilder {
+    pub(crate) fn new(client: APIClient, query: String) -> Self {
+        Self {
+            client,
+            endpoint: GRAPHQL_URL.to_string(),
+            query,
+            variables: None,
+            data_field: None,
+            timeout: None,
+        }
+    }
+
+    /// Sends the request somewhere other than `GRAPHQL_URL`.
+    pub fn endpoint<S: Into<String>>(mut self, endpoint: S) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// Sets the variables for the query. This can be a `json!()` value or any typed struct
+    /// that implements `Serialize`; serialization errors are returned from `send`.
+    pub fn variables<V: Serialize>(mut self, variables: V) -> Self {
+        self.variables = Some(serde_json::to_value(variables));
+        self
+    }
+
+    /// Deserializes only this field of the response `data`, rather than `data` itself.
+    pub fn data_field<S: Into<String>>(mut self, field: S) -> Self {
+        self.data_field = Some(field.into());
+        self
+    }
+
+    /// Overrides the client's timeout for this request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends the request, deserializing the response data into `T`.
+    pub fn send<T: DeserializeOwned>(self) -> Result<T, GraphQLError> {
+        let variables = self.variables.transpose().map_err(GraphQLError::InvalidVariables)?;
+
+        let body = match variables {
+            Some(variables) => json!({
+                "query": self.query,
+                "variables": variables,
+            }),
+
+            None => json!({
+                "query": self.query,
+            }),
+        };
+
+        let mut request = self.client.post(&self.endpoint);
+
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+
+        let debug_requests = self.client.debug_requests;
+        let operation = operation_name(&self.query);
+
+        if debug_requests {
+            tracing::debug!(
+                operation,
+                endpoint = self.endpoint,
+                variables = ?body.get("variables").map(variable_keys).unwrap_or_default(),
+                "Sending GraphQL request"
+            );
+        }
+
+        let started = Instant::now();
+        let result = request.send_json(&body);
+
+        if debug_requests {
+            let status = match &result {
+                Ok(response) => Some(response.status()),
+                Err(Error::Status(status, _)) => Some(*status),
+                Err(Error::Transport(_)) => None,
+            };
+
+            tracing::debug!(operation, ?status, elapsed = ?started.elapsed(), "Received GraphQL response");
+        }
+
+        let response = result.map_err(|error| match error {
+            Error::Status(status, response) => GraphQLError::Http {
+                status,
+                body: redact_body(response.into_string().unwrap_or_default()),
+            },
+
+            error => GraphQLError::Request(Box::new(error)),
+        })?;
+
+        let response: Value = serde_json::from_str(&response.into_string()?)?;
+
+        extract_data(response, self.data_field.as_deref())
+    }
+}
+
+/// Redacts an error response body, if it's JSON. Servers often reject bad requests with a 400
+/// and an `errors` array that echoes the variables back, and these bodies end up in logs.
+fn redact_body(body: String) -> String {
+    match serde_json::from_str::<Value>(&body) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        },
+
+        Err(_) => body,
+    }
+}
+
+/// Returns a name for the operation in `query`, for logging. Anonymous operations
+/// (`mutation ($report: ...) { reportOnlineGame ... }`) are named after their first field.
+fn operation_name(query: &str) -> &str {
+    fn identifier(input: &str) -> &str {
+        let input = input.trim_start();
+        let end = input
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(input.len());
+
+        &input[..end]
+    }
+
+    let query = query.trim_start();
+
+    for keyword in ["query", "mutation", "subscription"] {
+        if let Some(rest) = query.strip_prefix(keyword) {
+            let name = identifier(rest);
+
+            if !name.is_empty() {
+                return name;
+            }
+        }
+    }
+
+    match query.split_once('{') {
+        Some((_, selection)) => identifier(selection),
+        None => "",
+    }
+}
+
+/// Lists the keys in `variables` as dotted paths (e.g, `report.playKey`), without any of
+/// their values.
+fn variable_keys(variables: &Value) -> Vec<String> {
+    fn collect(value: &Value, prefix: &str, keys: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let path = match prefix {
+                        "" => key.clone(),
+                        prefix => format!("{prefix}.{key}"),
+                    };
+
+                    if !keys.contains(&path) {
+                        keys.push(path.clone());
+                    }
+
+                    collect(value, &path, keys);
+                }
+            },
+
+            Value::Array(values) => {
+                let path = format!("{prefix}[]");
+
+                for value in values {
+                    collect(value, &path, keys);
+                }
+            },
+
+            _ => {},
+        }
+    }
+
+    let mut keys = Vec::new();
+    collect(variables, "", &mut keys);
+    keys
+}
+
+/// Pulls the (optionally nested) data out of a GraphQL response, surfacing any errors
+/// the server reported.
+fn extract_data<T: DeserializeOwned>(mut response: Value, field: Option<&str>) -> Result<T, GraphQLError> {
+    if let Some(Value::Array(errors)) = response.get_mut("errors") {
+        if !errors.is_empty() {
+            // Error messages can echo the variables back, and these end up in logs.
+            errors.iter_mut().for_each(redact);
+            return Err(GraphQLError::Server(serde_json::to_string_pretty(errors)?));
+        }
+    }
+
+    let mut data = match response.get_mut("data") {
+        Some(data) => data.take(),
+        None => return Err(GraphQLError::MissingField("data".to_string())),
+    };
+
+    if let Some(field) = field {
+        data = match data.get_mut(field) {
+            Some(value) => value.take(),
+            None => return Err(GraphQLError::MissingField(field.to_string())),
+        };
+    }
+
+    Ok(serde_json::from_value(data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Report {
+        match_id: String,
+    }
+
+    #[test]
+    fn serializes_typed_variables() {
+        let builder = APIClient::new("test").graphql("query").variables(Report {
+            match_id: "mode.ranked-123".to_string(),
+        });
+
+        let variables = builder.variables.unwrap().unwrap();
+        assert_eq!(variables, json!({ "matchId": "mode.ranked-123" }));
+
+        // JSON object keys have to be strings, so this can't be serialized.
+        let bad_variables = HashMap::from([((1, 2), 3)]);
+        let error = APIClient::new("test")
+            .graphql("query")
+            .variables(bad_variables)
+            .send::<Value>()
+            .unwrap_err();
+
+        assert!(matches!(error, GraphQLError::InvalidVariables(_)));
+    }
+
+    #[test]
+    fn redacts_error_response_bodies() {
+        let body = r#"{"errors":[{"message":"Variable \"$report\" got invalid value","extensions":{"value":{"playKey":"secret-play-key"}}}]}"#;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0u8; 4096]);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 400 Bad Request\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+        });
+
+        let error = APIClient::new("test")
+            .graphql("query")
+            .endpoint(url)
+            .send::<Value>()
+            .unwrap_err();
+
+        match error {
+            GraphQLError::Http { status, body } => {
+                assert_eq!(status, 400);
+                assert!(body.contains("got invalid value"));
+                assert!(!body.contains("secret-play-key"));
+            },
+
+            error => panic!("Expected an HTTP error, got {error:?}"),
+        }
+
+        // Bodies that aren't JSON are kept as-is.
+        assert_eq!(redact_body("Bad Gateway".to_string()), "Bad Gateway");
+    }
+
+    #[test]
+    fn names_operations() {
+        assert_eq!(operation_name("query getUser($fbUid: String) { getUser }"), "getUser");
+        assert_eq!(
+            operation_name("\n mutation ($report: OnlineGameReportInput!) {\n reportOnlineGame (report: $report) {"),
+            "reportOnlineGame"
+        );
+        assert_eq!(operation_name("{ __typename }"), "__typename");
+    }
+
+    #[test]
+    fn logs_variable_keys_without_values() {
+        let variables = json!({
+            "report": {
+                "fbUid": "secret-uid",
+                "playKey": "secret-play-key",
+                "players": [{ "slot": 0 }, { "slot": 1 }],
+            }
+        });
+
+        let keys = format!("{:?}", variable_keys(&variables));
+
+        assert_eq!(
+            variable_keys(&variables),
+            [
+                "report",
+                "report.fbUid",
+                "report.playKey",
+                "report.players",
+                "report.players[].slot"
+            ]
+        );
+        assert!(!keys.contains("secret"));
+    }
+
+    #[test]
+    fn extracts_nested_data_fields() {
+        let response = json!({ "data": { "abandonOnlineGame": true } });
+        let value: bool = extract_data(response, Some("abandonOnlineGame")).unwrap();
+        assert!(value);
+    }
+
+    #[test]
+    fn surfaces_server_errors() {
+        let response = json!({ "data": null, "errors": [{ "message": "nope" }] });
+        let error = extract_data::<Value>(response, None).unwrap_err();
+        assert!(matches!(error, GraphQLError::Server(message) if message.contains("nope")));
+    }
+
+    #[test]
+    fn reports_missing_fields() {
+        let error = extract_data::<Value>(json!({}), None).unwrap_err();
+        assert!(matches!(error, GraphQLError::MissingField(field) if field == "data"));
+
+        let error = extract_data::<Value>(json!({ "data": {} }), Some("reportOnlineGame")).unwrap_err();
+        assert!(matches!(error, GraphQLError::MissingField(field) if field == "reportOnlineGame"));
+    }
+}
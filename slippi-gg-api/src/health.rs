@@ -0,0 +1,262 @@
+//! Health checks against the slippi.gg API, for diagnostics (e.g, a "Test Connection" button).
+//!
+//! Checks run on a background thread: callers start one via `HealthMonitor::start` and poll
+//! `HealthMonitor::state` for the outcome, so nothing on the UI or game thread ever blocks
+//! on the network.
+
+use std::error::Error as StdError;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::{APIClient, GraphQLError};
+
+/// How long a health check waits on the server before reporting `HealthCheckStatus::TimedOut`.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The outcome of a health check against the GraphQL endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HealthCheckStatus {
+    Ok,
+    AuthFailure,
+    NetworkFailure,
+    ServerError,
+
+    /// The server didn't respond within `HEALTH_CHECK_TIMEOUT`. This is reported separately
+    /// from `NetworkFailure`, since a slow server and an unreachable one call for different fixes.
+    TimedOut,
+}
+
+/// The result of a finished health check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HealthCheck {
+    pub status: HealthCheckStatus,
+
+    /// The round-trip time of the check, if the server responded at all (even with an error).
+    pub latency: Option<Duration>,
+}
+
+/// Where the most recent health check started by a `HealthMonitor` is at.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HealthCheckState {
+    #[default]
+    NotStarted,
+    Pending,
+    Finished(HealthCheck),
+}
+
+/// Runs health checks in the background and holds on to the latest result.
+///
+/// This is cheap to clone, and clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct HealthMonitor {
+    state: Arc<Mutex<HealthCheckState>>,
+}
+
+impl HealthMonitor {
+    /// Creates a new monitor that hasn't run any checks yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a health check against the client's configured health check URL on a background
+    /// thread, and returns immediately. If a check is already in flight, this does nothing -
+    /// poll `state` for its result instead.
+    pub fn start(&self, api_client: &APIClient) {
+        {
+            let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+
+            if *state == HealthCheckState::Pending {
+                return;
+            }
+
+            *state = HealthCheckState::Pending;
+        }
+
+        let api_client = api_client.clone();
+        let state = self.state.clone();
+
+        let spawned = thread::Builder::new()
+            .name("SlippiAPIHealthCheckThread".into())
+            .spawn(move || {
+                let check = run_health_check(&api_client, &api_client.health_check_url, HEALTH_CHECK_TIMEOUT);
+                tracing::info!(status = ?check.status, latency = ?check.latency, "API health check finished");
+
+                *state.lock().unwrap_or_else(|error| error.into_inner()) = HealthCheckState::Finished(check);
+            });
+
+        if let Err(error) = spawned {
+            tracing::error!(?error, "Unable to spawn health check thread");
+
+            *self.state.lock().unwrap_or_else(|error| error.into_inner()) = HealthCheckState::Finished(HealthCheck {
+                status: HealthCheckStatus::NetworkFailure,
+                latency: None,
+            });
+        }
+    }
+
+    /// Returns the state of the most recently started health check. This never blocks on
+    /// the network.
+    pub fn state(&self) -> HealthCheckState {
+        *self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// Runs a minimal GraphQL query against `endpoint`, blocking until it completes or `timeout`
+/// elapses.
+fn run_health_check(api_client: &APIClient, endpoint: &str, timeout: Duration) -> HealthCheck {
+    let started = Instant::now();
+
+    let result = api_client
+        .graphql("query { __typename }")
+        .endpoint(endpoint)
+        .timeout(timeout)
+        .send::<Value>();
+
+    let status = match result {
+        Ok(_) => HealthCheckStatus::Ok,
+        Err(error) => classify_health_check_error(&error),
+    };
+
+    let latency = match status {
+        HealthCheckStatus::NetworkFailure | HealthCheckStatus::TimedOut => None,
+        _ => Some(started.elapsed()),
+    };
+
+    HealthCheck { status, latency }
+}
+
+/// Maps a GraphQL error onto the coarse categories a health check reports.
+fn classify_health_check_error(error: &GraphQLError) -> HealthCheckStatus {
+    match error {
+        GraphQLError::Http { status: 401 | 403, .. } => HealthCheckStatus::AuthFailure,
+        GraphQLError::Http { .. } => HealthCheckStatus::ServerError,
+
+        GraphQLError::Request(error) if is_timeout(error.source()) => HealthCheckStatus::TimedOut,
+        GraphQLError::IO(error) if is_timeout(Some(error)) => HealthCheckStatus::TimedOut,
+
+        // The health check query has no variables, but if it ever fails to serialize them
+        // then nothing reached the server.
+        GraphQLError::Request(_) | GraphQLError::IO(_) | GraphQLError::InvalidVariables(_) => HealthCheckStatus::NetworkFailure,
+
+        GraphQLError::InvalidResponse(_) | GraphQLError::Server(_) | GraphQLError::MissingField(_) => {
+            HealthCheckStatus::ServerError
+        },
+    }
+}
+
+/// Whether `error` is an IO error from a connect or read timing out. Depending on the
+/// platform, socket timeouts surface as either `TimedOut` or `WouldBlock`.
+fn is_timeout(error: Option<&(dyn StdError + 'static)>) -> bool {
+    match error.and_then(|error| error.downcast_ref::<io::Error>()) {
+        Some(error) => matches!(error.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::ClientConfig;
+
+    /// Serves `response` to a single connection on a local port, returning its URL.
+    fn serve_once(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0u8; 4096]);
+            let _ = stream.write_all(response.as_bytes());
+        });
+
+        url
+    }
+
+    #[test]
+    fn classifies_health_check_results() {
+        let api_client = APIClient::new("test");
+        let timeout = Duration::from_secs(5);
+
+        let url = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 31\r\n\r\n{\"data\":{\"__typename\":\"Query\"}}");
+        let check = run_health_check(&api_client, &url, timeout);
+        assert_eq!(check.status, HealthCheckStatus::Ok);
+        assert!(check.latency.unwrap() < timeout);
+
+        let url = serve_once("HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n");
+        assert_eq!(
+            run_health_check(&api_client, &url, timeout).status,
+            HealthCheckStatus::AuthFailure
+        );
+
+        // The server responded, so there's still a latency to report.
+        let url = serve_once("HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n");
+        let check = run_health_check(&api_client, &url, timeout);
+        assert_eq!(check.status, HealthCheckStatus::ServerError);
+        assert!(check.latency.is_some());
+
+        // Nothing listening here anymore, so the connection is refused.
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let url = format!("http://{closed}");
+        let check = run_health_check(&api_client, &url, timeout);
+        assert_eq!(check.status, HealthCheckStatus::NetworkFailure);
+        assert_eq!(check.latency, None);
+
+        let error = GraphQLError::Server("errors".to_string());
+        assert_eq!(classify_health_check_error(&error), HealthCheckStatus::ServerError);
+    }
+
+    #[test]
+    fn unresponsive_servers_time_out() {
+        // Accepts connections but never responds.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+
+        let started = Instant::now();
+        let check = run_health_check(&APIClient::new("test"), &url, Duration::from_millis(200));
+
+        assert_eq!(check.status, HealthCheckStatus::TimedOut);
+        assert_eq!(check.latency, None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        drop(listener);
+    }
+
+    #[test]
+    fn monitors_report_checks_in_the_background() {
+        let api_client = APIClient::with_config(
+            "test",
+            ClientConfig {
+                health_check_url: serve_once("HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n"),
+                ..ClientConfig::default()
+            },
+        );
+
+        let monitor = HealthMonitor::new();
+        assert_eq!(monitor.state(), HealthCheckState::NotStarted);
+
+        monitor.start(&api_client);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut state = monitor.state();
+
+        while state == HealthCheckState::Pending && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            state = monitor.state();
+        }
+
+        assert!(matches!(
+            state,
+            HealthCheckState::Finished(HealthCheck {
+                status: HealthCheckStatus::AuthFailure,
+                latency: Some(_),
+            })
+        ));
+    }
+}
@@ -1,28 +1,150 @@
 use std::io;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
 use std::time::Duration;
 
 use ureq::{Agent, AgentBuilder, Resolver};
 
+mod graphql;
+pub use graphql::{GraphQLBuilder, GraphQLError, GRAPHQL_URL};
+
+mod health;
+pub use health::{HealthCheck, HealthCheckState, HealthCheckStatus, HealthMonitor};
+
+pub mod redact;
+
 /// Re-export `ureq::Error` for simplicity.
 pub type Error = ureq::Error;
 
-/// A DNS resolver that only accepts IPV4 connections.
-struct Ipv4Resolver;
+/// Re-export `ureq::Request` for simplicity.
+pub type Request = ureq::Request;
+
+/// Default timeout for establishing a connection, and for individual socket reads.
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// We keep `5` idle connections by default to mimic how CURL was configured in the old C++ logic.
+const DEFAULT_IDLE_CONNECTIONS: usize = 5;
+
+/// Configuration for an `APIClient`.
+///
+/// Note that the `Default` implementation differs from what `APIClient::new` used to do: rather
+/// than a single 5s deadline on the whole request, connecting and each socket read are now
+/// bounded separately (5s each), and there's no overall deadline unless `timeout` is set. A
+/// response that keeps trickling in can therefore take longer than 5s in total.
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// When set, every host lookup logs (at debug level) the socket addresses it resolved to,
+    /// along with any that the resolver filtered out. This doesn't change which addresses are
+    /// used - it just makes DNS/IPV6 issues visible when triaging connection failures.
+    pub log_dns_resolution: bool,
+
+    /// Which resolved addresses the client may connect to.
+    pub resolver_policy: ResolverPolicy,
+
+    /// How long to wait on TCP/TLS setup before giving up. Keeping this short means a dead
+    /// host (or no network at all) fails fast.
+    pub timeout_connect: Duration,
+
+    /// How long to wait on each read from an established connection, which gives slow but
+    /// otherwise healthy responses room to complete.
+    pub timeout_read: Duration,
+
+    /// An overall deadline for each request, covering everything from connecting to reading
+    /// the full response. `None` leaves requests bounded only by the connect and read timeouts.
+    pub timeout: Option<Duration>,
+
+    /// How many idle connections the client keeps pooled for reuse.
+    pub idle_connections: usize,
+
+    /// When set, every GraphQL request logs (at debug level) its operation name, the keys of
+    /// its variables, and the response status and timing. Variable values are never logged,
+    /// so play keys and the like stay out of user logs.
+    pub debug_requests: bool,
+
+    /// Where `HealthMonitor` sends its requests.
+    pub health_check_url: String,
+}
 
-impl Resolver for Ipv4Resolver {
-    /// Forces IPV4 addresses only.
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            log_dns_resolution: false,
+            resolver_policy: ResolverPolicy::default(),
+            timeout_connect: DEFAULT_TIMEOUT,
+            timeout_read: DEFAULT_TIMEOUT,
+            timeout: None,
+            idle_connections: DEFAULT_IDLE_CONNECTIONS,
+            debug_requests: false,
+            health_check_url: GRAPHQL_URL.to_string(),
+        }
+    }
+}
+
+/// Controls which resolved addresses the client is allowed to connect to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResolverPolicy {
+    /// Only ever connect over IPV4. This works around upstream issues with GCP flex
+    /// instances and IPV6, but means IPV6-only hosts can't be reached at all.
+    #[default]
+    Ipv4Only,
+
+    /// Prefer IPV4, but fall back to IPV6 addresses when a host has no IPV4 records.
+    PreferIpv4,
+
+    /// Use whatever the system resolver returns, as-is.
+    System,
+}
+
+/// A DNS resolver that filters addresses from an inner resolver according to a `ResolverPolicy`.
+struct PolicyResolver<R> {
+    inner: R,
+    policy: ResolverPolicy,
+    log_dns_resolution: bool,
+}
+
+/// Performs the actual lookup via the system resolver.
+fn system_resolve(netloc: &str) -> io::Result<Vec<SocketAddr>> {
+    ToSocketAddrs::to_socket_addrs(netloc).map(Iterator::collect)
+}
+
+impl<R: Resolver> Resolver for PolicyResolver<R> {
     fn resolve(&self, netloc: &str) -> io::Result<Vec<SocketAddr>> {
-        ToSocketAddrs::to_socket_addrs(netloc).map(|iter| {
-            let vec = iter.filter(|s| s.is_ipv4()).collect::<Vec<SocketAddr>>();
+        let addrs = match self.inner.resolve(netloc) {
+            Ok(addrs) => addrs,
+
+            Err(error) => {
+                if self.log_dns_resolution {
+                    tracing::debug!(netloc, ?error, "Failed to resolve host");
+                }
+
+                return Err(error);
+            },
+        };
+
+        let (ipv4, ipv6): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs.iter().copied().partition(|s| s.is_ipv4());
+
+        let (vec, filtered) = match self.policy {
+            ResolverPolicy::Ipv4Only => (ipv4, ipv6),
 
-            if vec.is_empty() {
-                tracing::warn!("Failed to get any IPV4 addresses. Does the DNS server support it?");
-            }
+            ResolverPolicy::PreferIpv4 if ipv4.is_empty() => {
+                tracing::warn!(netloc, "No IPV4 addresses found, falling back to IPV6");
+                (ipv6, Vec::new())
+            },
 
-            vec
-        })
+            ResolverPolicy::PreferIpv4 => (ipv4, ipv6),
+            ResolverPolicy::System => (addrs, Vec::new()),
+        };
+
+        if self.log_dns_resolution {
+            tracing::debug!(netloc, resolved = ?vec, ?filtered, "Resolved host addresses");
+        }
+
+        if vec.is_empty() && self.policy == ResolverPolicy::Ipv4Only {
+            tracing::warn!("Failed to get any IPV4 addresses. Does the DNS server support it?");
+        }
+
+        Ok(vec)
     }
 }
 
@@ -36,14 +158,23 @@ impl Resolver for Ipv4Resolver {
 /// this type. You can also clone this with little cost, and pass it freely
 /// to other threads, as it manages itself under the hood with `Arc`.
 #[derive(Clone, Debug)]
-pub struct APIClient(Agent);
+pub struct APIClient {
+    agent: Agent,
+    debug_requests: bool,
+    health_check_url: Arc<str>,
+}
 
 impl APIClient {
     /// Creates and initializes a new APIClient.
     ///
-    /// The returned client will only resolve to IPV4 addresses at the moment
+    /// The returned client will only resolve to IPV4 addresses (see `ResolverPolicy::Ipv4Only`)
     /// due to upstream issues with GCP flex instances and IPV6.
     pub fn new(slippi_semver: &str) -> Self {
+        Self::with_config(slippi_semver, ClientConfig::default())
+    }
+
+    /// Creates and initializes a new APIClient with the provided configuration.
+    pub fn with_config(slippi_semver: &str, config: ClientConfig) -> Self {
         let _build = "";
 
         #[cfg(feature = "mainline")]
@@ -55,17 +186,35 @@ impl APIClient {
         #[cfg(feature = "playback")]
         let _build = "playback";
 
-        // We set `max_idle_connections` to `5` to mimic how CURL was configured in
-        // the old C++ logic. This gets cloned and passed down into modules so that
-        // the underlying connection pool is shared.
-        let http_client = AgentBuilder::new()
-            .resolver(Ipv4Resolver)
-            .max_idle_connections(5)
-            .timeout(Duration::from_millis(5000))
-            .user_agent(&format!("SlippiDolphin/{} ({}) (Rust)", _build, slippi_semver))
-            .build();
-
-        Self(http_client)
+        // This gets cloned and passed down into modules so that the underlying
+        // connection pool is shared.
+        let mut builder = AgentBuilder::new()
+            .resolver(PolicyResolver {
+                inner: system_resolve,
+                policy: config.resolver_policy,
+                log_dns_resolution: config.log_dns_resolution,
+            })
+            .max_idle_connections(config.idle_connections)
+            .timeout_connect(config.timeout_connect)
+            .timeout_read(config.timeout_read)
+            .user_agent(&format!("SlippiDolphin/{} ({}) (Rust)", _build, slippi_semver));
+
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        Self {
+            agent: builder.build(),
+            debug_requests: config.debug_requests,
+            health_check_url: config.health_check_url.into(),
+        }
+    }
+}
+
+impl APIClient {
+    /// Starts building a GraphQL request for `query`. By default this goes to `GRAPHQL_URL`.
+    pub fn graphql<Q: Into<String>>(&self, query: Q) -> GraphQLBuilder {
+        GraphQLBuilder::new(self.clone(), query.into())
     }
 }
 
@@ -73,12 +222,76 @@ impl Deref for APIClient {
     type Target = Agent;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.agent
     }
 }
 
 impl DerefMut for APIClient {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.agent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn overall_timeout_bounds_requests() {
+        // Accepts connections but never responds, so only a timeout gets us out.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let client = APIClient::with_config(
+            "test",
+            ClientConfig {
+                timeout_read: Duration::from_secs(30),
+                timeout: Some(Duration::from_millis(200)),
+                ..ClientConfig::default()
+            },
+        );
+
+        let started = Instant::now();
+        assert!(client.get(&url).call().is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        drop(listener);
+    }
+
+    /// Stands in for DNS, returning the same canned addresses for every host.
+    struct FakeResolver(Vec<SocketAddr>);
+
+    impl Resolver for FakeResolver {
+        fn resolve(&self, _netloc: &str) -> io::Result<Vec<SocketAddr>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn resolve(policy: ResolverPolicy, addrs: &[&str]) -> Vec<SocketAddr> {
+        let resolver = PolicyResolver {
+            inner: FakeResolver(addrs.iter().map(|addr| addr.parse().unwrap()).collect()),
+            policy,
+            log_dns_resolution: true,
+        };
+
+        resolver.resolve("slippi.gg:443").unwrap()
+    }
+
+    #[test]
+    fn resolver_policies_filter_addresses() {
+        let v4: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        let v6: SocketAddr = "[::1]:443".parse().unwrap();
+        let both = ["[::1]:443", "10.0.0.1:443"];
+
+        assert_eq!(resolve(ResolverPolicy::Ipv4Only, &both), vec![v4]);
+        assert_eq!(resolve(ResolverPolicy::Ipv4Only, &["[::1]:443"]), vec![]);
+
+        assert_eq!(resolve(ResolverPolicy::PreferIpv4, &both), vec![v4]);
+        assert_eq!(resolve(ResolverPolicy::PreferIpv4, &["[::1]:443"]), vec![v6]);
+
+        assert_eq!(resolve(ResolverPolicy::System, &both), vec![v6, v4]);
     }
 }
@@ -0,0 +1,84 @@
+//! Helpers for keeping user credentials (play keys, uids, signed URLs) out of logs.
+
+use serde_json::Value;
+
+/// Keys whose values identify or authenticate a user, and so should never be logged.
+pub const SENSITIVE_KEYS: [&str; 2] = ["playKey", "fbUid"];
+
+/// What redacted values are replaced with.
+const REDACTED: &str = "[redacted]";
+
+/// Replaces the value of any `SENSITIVE_KEYS` in `value`, at any depth, with a placeholder.
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SENSITIVE_KEYS.contains(&key.as_str()) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        },
+
+        Value::Array(values) => values.iter_mut().for_each(redact),
+
+        _ => {},
+    }
+}
+
+/// Cuts an identifier (e.g, a uid) down to a short prefix, which is still enough to
+/// correlate log lines without logging the whole thing.
+pub fn redact_id(id: &str) -> String {
+    match id.char_indices().nth(4) {
+        Some((end, _)) => format!("{}…", &id[..end]),
+        None => REDACTED.to_string(),
+    }
+}
+
+/// Strips the query string (and fragment) from `url`. Signed URLs, such as the ones replays
+/// are uploaded to, carry their credentials there.
+pub fn strip_query(url: &str) -> &str {
+    match url.find(['?', '#']) {
+        Some(index) => &url[..index],
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn redacts_sensitive_values() {
+        let mut value = json!({
+            "report": {
+                "fbUid": "some-uid",
+                "playKey": "some-play-key",
+                "players": [{ "fbUid": "other-uid", "slotType": 0 }],
+            }
+        });
+
+        redact(&mut value);
+
+        assert_eq!(
+            value,
+            json!({
+                "report": {
+                    "fbUid": REDACTED,
+                    "playKey": REDACTED,
+                    "players": [{ "fbUid": REDACTED, "slotType": 0 }],
+                }
+            })
+        );
+
+        assert_eq!(redact_id("abcdefgh"), "abcd…");
+        assert_eq!(redact_id("abc"), REDACTED);
+
+        let url = "https://storage.googleapis.com/replays/123.slp?X-Goog-Signature=abc#frag";
+        assert_eq!(strip_query(url), "https://storage.googleapis.com/replays/123.slp");
+        assert_eq!(strip_query("https://slippi.gg"), "https://slippi.gg");
+    }
+}
@@ -1,10 +1,12 @@
 //! This module contains data models and helper methods for handling user authentication
 //! from within Slippi Dolphin.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 
 // use dolphin_integrations::Log;
+use slippi_gg_api::redact::redact_id;
 use slippi_gg_api::APIClient;
 
 mod chat;
@@ -13,7 +15,11 @@ pub use chat::DEFAULT_CHAT_MESSAGES;
 mod watcher;
 use watcher::UserInfoWatcher;
 
-const USER_API_URL: &'static str = "https://users-rest-dot-slippi.uc.r.appspot.com/user";
+const USER_API_URL: &str = "https://users-rest-dot-slippi.uc.r.appspot.com/user";
+
+/// How many consecutive times `user.json` can exist but fail to be read before we consider
+/// it blocked (e.g, locked by antivirus or another process).
+const BLOCKED_READ_ATTEMPTS: u32 = 5;
 
 /// The core payload that represents user information. This type is expected to conform
 /// to the same definition that the remote server uses.
@@ -61,6 +67,8 @@ pub struct UserManager {
     user_json_path: Arc<PathBuf>,
     slippi_semver: String,
     watcher: Arc<Mutex<UserInfoWatcher>>,
+    read_failures: Arc<AtomicU32>,
+    config_folder_writable: bool,
 }
 
 impl UserManager {
@@ -74,6 +82,12 @@ impl UserManager {
     // how some things get persisted from the Dolphin side. Not a big deal to thread it for now.
     pub fn new(api_client: APIClient, user_json_path: PathBuf, slippi_semver: String) -> Self {
         let user = Arc::new(Mutex::new(UserInfo::default()));
+
+        let config_folder_writable = match user_json_path.parent() {
+            Some(folder) => prepare_config_folder(folder),
+            None => false,
+        };
+
         let user_json_path = Arc::new(user_json_path);
         let watcher = Arc::new(Mutex::new(UserInfoWatcher::new()));
 
@@ -83,9 +97,25 @@ impl UserManager {
             user_json_path,
             slippi_semver,
             watcher,
+            read_failures: Arc::new(AtomicU32::new(0)),
+            config_folder_writable,
         }
     }
 
+    /// Returns whether `user.json` exists but has repeatedly failed to be read - e.g, because
+    /// antivirus or another process has it locked. A `user.json` that simply doesn't exist yet
+    /// is not considered blocked.
+    pub fn is_user_json_blocked(&self) -> bool {
+        self.read_failures.load(Ordering::Relaxed) >= BLOCKED_READ_ATTEMPTS
+    }
+
+    /// Returns whether the folder holding `user.json` exists and could be written to when
+    /// this manager was created. If not, anything we'd normally persist there only lives
+    /// in memory for this session, and the UI may want to let the user know.
+    pub fn is_config_folder_writable(&self) -> bool {
+        self.config_folder_writable
+    }
+
     /// User info is held behind a Mutex as we access it from multiple threads. To read data
     /// from the user info, you can pass a closure to this method to extract whatever you need. If
     /// the user is not authenticated, then the underlying user is `None` and the closure will
@@ -139,7 +169,40 @@ impl UserManager {
     /// Runs the `attempt_login` function on the calling thread. If you need this to run in the
     /// background, you want `watch_for_login` instead.
     pub fn attempt_login(&self) -> bool {
-        attempt_login(&self.api_client, &self.user, &self.user_json_path, &self.slippi_semver)
+        attempt_login(
+            &self.api_client,
+            &self.user,
+            &self.user_json_path,
+            &self.read_failures,
+            &self.slippi_semver,
+        )
+    }
+
+    /// Re-fetches the current user's info from the server on a background thread, picking up
+    /// anything that changed server-side (display name, chat messages, etc) mid-session.
+    ///
+    /// Does nothing if the user isn't logged in.
+    pub fn refresh(&self) {
+        let uid = self.get(|user| user.uid.clone());
+
+        if uid.is_empty() {
+            tracing::warn!("Attempted to refresh user info while not logged in");
+            return;
+        }
+
+        let api_client = self.api_client.clone();
+        let user = self.user.clone();
+        let slippi_semver = self.slippi_semver.clone();
+
+        let spawned = std::thread::Builder::new()
+            .name("SlippiUserRefreshThread".into())
+            .spawn(move || {
+                overwrite_from_server(&api_client, &user, uid, &slippi_semver);
+            });
+
+        if let Err(error) = spawned {
+            tracing::error!(?error, "Unable to spawn user refresh thread");
+        }
     }
 
     /// Kicks off a background handler for processing user authentication.
@@ -150,6 +213,7 @@ impl UserManager {
             self.api_client.clone(),
             self.user_json_path.clone(),
             self.user.clone(),
+            self.read_failures.clone(),
             &self.slippi_semver,
         );
     }
@@ -213,11 +277,75 @@ impl UserManager {
     }
 }
 
+/// Ensures that the user config folder exists, creating it if necessary, and probes whether
+/// it can be written to.
+///
+/// Returns `false` (after logging why) if the folder is missing and can't be created, or if
+/// it can't be written to.
+fn prepare_config_folder(folder: &Path) -> bool {
+    if !folder.is_dir() {
+        match std::fs::create_dir_all(folder) {
+            Ok(()) => tracing::info!(?folder, "Created missing user config folder"),
+
+            Err(error) => {
+                tracing::warn!(
+                    ?error,
+                    ?folder,
+                    "Unable to create user config folder, settings will not persist"
+                );
+                return false;
+            },
+        }
+    }
+
+    let probe = folder.join(".slippi-write-check");
+
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            true
+        },
+
+        Err(error) => {
+            tracing::warn!(
+                ?error,
+                ?folder,
+                "User config folder is not writable, settings will not persist"
+            );
+            false
+        },
+    }
+}
+
 /// Checks for the existence of a `user.json` file and, if found, attempts to load and parse it.
 ///
 /// This returns a `bool` value so that the background thread can know whether to stop checking.
-fn attempt_login(api_client: &APIClient, user: &Arc<Mutex<UserInfo>>, user_json_path: &PathBuf, slippi_semver: &str) -> bool {
-    match std::fs::read_to_string(user_json_path) {
+fn attempt_login(
+    api_client: &APIClient,
+    user: &Arc<Mutex<UserInfo>>,
+    user_json_path: &PathBuf,
+    read_failures: &AtomicU32,
+    slippi_semver: &str,
+) -> bool {
+    let contents = std::fs::read_to_string(user_json_path);
+
+    match &contents {
+        Err(error) if is_blocked_read(error) => {
+            let failures = read_failures.fetch_add(1, Ordering::Relaxed) + 1;
+
+            if failures == BLOCKED_READ_ATTEMPTS {
+                tracing::warn!(
+                    ?error,
+                    failures,
+                    "user.json exists but keeps failing to read, something may be blocking it"
+                );
+            }
+        },
+
+        _ => read_failures.store(0, Ordering::Relaxed),
+    }
+
+    match contents {
         Ok(contents) => match serde_json::from_str::<UserInfo>(&contents) {
             Ok(mut info) => {
                 info.sanitize();
@@ -243,7 +371,7 @@ fn attempt_login(api_client: &APIClient, user: &Arc<Mutex<UserInfo>>, user_json_
         // Filesystem I/O error
         Err(error) => {
             // A not-found file just means they haven't logged in yet... presumably.
-            if error.kind() != std::io::ErrorKind::NotFound {
+            if is_blocked_read(&error) {
                 tracing::error!(?error, "Unable to read user.json");
             }
 
@@ -252,37 +380,74 @@ fn attempt_login(api_client: &APIClient, user: &Arc<Mutex<UserInfo>>, user_json_
     }
 }
 
-/// The core payload that represents user information. This type is expected to conform
-/// to the same definition that the remote server uses.
+/// Whether a failure to read `user.json` means something is in the way of the file, as opposed
+/// to it simply not existing yet (i.e, the user hasn't logged in).
+fn is_blocked_read(error: &std::io::Error) -> bool {
+    error.kind() != std::io::ErrorKind::NotFound
+}
+
+/// The payload the user info endpoint returns.
+///
+/// Anything other than the uid may be missing (or `null`) in the response, in which case the
+/// corresponding local value is left as-is rather than failing the whole payload.
 #[derive(Debug, Default, serde::Deserialize)]
 struct UserInfoAPIResponse {
     pub uid: String,
 
-    #[serde(alias = "displayName")]
-    pub display_name: String,
+    #[serde(alias = "displayName", default)]
+    pub display_name: Option<String>,
 
-    #[serde(alias = "connectCode")]
-    pub connect_code: String,
+    #[serde(alias = "connectCode", default)]
+    pub connect_code: Option<String>,
 
-    #[serde(alias = "latestVersion")]
-    pub latest_version: String,
+    #[serde(alias = "latestVersion", default)]
+    pub latest_version: Option<String>,
 
-    #[serde(alias = "chatMessages")]
-    pub chat_messages: Vec<String>,
+    #[serde(alias = "chatMessages", default)]
+    pub chat_messages: Option<Vec<String>>,
+}
+
+impl UserInfoAPIResponse {
+    /// Copies whatever fields the server returned onto `user`.
+    fn apply_to(self, user: &mut UserInfo) {
+        user.uid = self.uid;
+
+        if let Some(display_name) = self.display_name {
+            user.display_name = display_name;
+        }
+
+        if let Some(connect_code) = self.connect_code {
+            user.connect_code = connect_code;
+        }
+
+        if let Some(latest_version) = self.latest_version {
+            user.latest_version = latest_version;
+        }
+
+        if let Some(chat_messages) = self.chat_messages {
+            user.chat_messages = Some(chat_messages);
+        }
+
+        user.sanitize();
+    }
 }
 
 /// Calls out to the Slippi server and fetches the user info, patching up the user info object
 /// with any returned information.
 fn overwrite_from_server(api_client: &APIClient, user: &Arc<Mutex<UserInfo>>, uid: String, slippi_semver: &str) {
+    // Beta builds talk to the beta user service, so they see beta versions as the latest.
     let is_beta = match slippi_semver.contains("beta") {
         true => "-beta",
         false => "",
     };
 
-    // @TODO: Switch this to a GraphQL call? Likely a Fizzi/Nikki task.
+    // @TODO: Switch this to a GraphQL call? Likely a Fizzi/Nikki task. This stays on REST until a
+    // `getUser` query has been checked against the production schema, since a guessed one fails
+    // every login refresh.
     let url = format!("{USER_API_URL}{is_beta}/{uid}?additionalFields=chatMessages");
 
-    tracing::warn!(?url, "Fetching user info");
+    // The URL has the uid in it, so it's not logged directly.
+    tracing::warn!(uid = redact_id(&uid), is_beta, "Fetching user info");
 
     match api_client.get(&url).call() {
         Ok(response) => match response.into_string() {
@@ -290,13 +455,17 @@ fn overwrite_from_server(api_client: &APIClient, user: &Arc<Mutex<UserInfo>>, ui
                 Ok(info) => {
                     let mut lock = user.lock().expect("Unable to lock user in attempt_login");
 
-                    lock.uid = info.uid;
-                    lock.display_name = info.display_name;
-                    lock.connect_code = info.connect_code;
-                    lock.latest_version = info.latest_version;
-                    lock.chat_messages = Some(info.chat_messages);
-
-                    (*lock).sanitize();
+                    // The user may have logged out (or switched accounts) while the request was in
+                    // flight, in which case this response is stale and shouldn't be applied.
+                    if lock.uid != uid {
+                        tracing::warn!(
+                            uid = redact_id(&uid),
+                            "User changed while fetching user info, discarding response"
+                        );
+                        return;
+                    }
+
+                    info.apply_to(&mut lock);
                 },
 
                 Err(error) => {
@@ -310,10 +479,100 @@ fn overwrite_from_server(api_client: &APIClient, user: &Arc<Mutex<UserInfo>>, ui
             },
         },
 
-        // `error` is an enum, where one branch will contain the status code if relevant.
-        // We log the debug representation to just see it all.
+        // `error` is an enum, where one branch will contain the status code if relevant. Its
+        // debug output includes the request URL (and so the uid), so only log the kind.
         Err(error) => {
-            tracing::error!(?error, "API call for user info failed");
+            tracing::error!(error = %error.kind(), "API call for user info failed");
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_user_info_responses_keep_existing_values() {
+        let mut user = UserInfo {
+            uid: "uid".to_string(),
+            display_name: "Player".to_string(),
+            connect_code: "PLYR#123".to_string(),
+            latest_version: "3.0.0".to_string(),
+            ..UserInfo::default()
+        };
+
+        let body = r#"{ "uid": "uid", "displayName": "Renamed", "chatMessages": null }"#;
+        let info: UserInfoAPIResponse = serde_json::from_str(body).unwrap();
+        info.apply_to(&mut user);
+
+        assert_eq!(user.display_name, "Renamed");
+        assert_eq!(user.connect_code, "PLYR#123");
+        assert_eq!(user.latest_version, "3.0.0");
+        assert_eq!(user.chat_messages, Some(chat::default()));
+    }
+
+    /// Returns a unique, not-yet-existing path under the system temp directory.
+    fn scratch_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("slippi-user-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn only_non_missing_read_errors_count_as_blocked() {
+        use std::io::{Error, ErrorKind};
+
+        assert!(!is_blocked_read(&Error::from(ErrorKind::NotFound)));
+        assert!(is_blocked_read(&Error::from(ErrorKind::PermissionDenied)));
+    }
+
+    #[test]
+    fn repeated_read_failures_mark_user_json_blocked() {
+        let api_client = APIClient::new("test");
+        let user = Arc::new(Mutex::new(UserInfo::default()));
+        let read_failures = AtomicU32::new(0);
+
+        // Not found: keep quietly polling.
+        let missing = scratch_path("user-json-missing");
+        for _ in 0..BLOCKED_READ_ATTEMPTS {
+            assert!(!attempt_login(&api_client, &user, &missing, &read_failures, "test"));
+        }
+        assert_eq!(read_failures.load(Ordering::Relaxed), 0);
+
+        // Present but unreadable (here, a directory): counts toward being blocked.
+        let unreadable = scratch_path("user-json-unreadable");
+        std::fs::create_dir_all(&unreadable).unwrap();
+        for _ in 0..BLOCKED_READ_ATTEMPTS {
+            assert!(!attempt_login(&api_client, &user, &unreadable, &read_failures, "test"));
+        }
+        assert_eq!(read_failures.load(Ordering::Relaxed), BLOCKED_READ_ATTEMPTS);
+
+        // Once it's gone again, the count resets.
+        std::fs::remove_dir_all(&unreadable).unwrap();
+        assert!(!attempt_login(&api_client, &user, &unreadable, &read_failures, "test"));
+        assert_eq!(read_failures.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn missing_config_folder_is_created() {
+        let root = scratch_path("missing");
+        let folder = root.join("nested").join("User");
+
+        assert!(prepare_config_folder(&folder));
+        assert!(folder.is_dir());
+
+        // The write probe should not be left behind.
+        assert_eq!(std::fs::read_dir(&folder).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn unwritable_config_folder_is_detected() {
+        // A regular file where the folder should be means it can be neither created nor written to.
+        let blocker = scratch_path("blocked");
+        std::fs::write(&blocker, b"").unwrap();
+
+        assert!(!prepare_config_folder(&blocker.join("User")));
+
+        std::fs::remove_file(&blocker).unwrap();
+    }
+}
@@ -1,9 +1,11 @@
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use slippi_gg_api::APIClient;
 
 use super::{attempt_login, UserInfo};
@@ -31,6 +33,7 @@ impl UserInfoWatcher {
         api_client: APIClient,
         user_json_path: Arc<PathBuf>,
         user: Arc<Mutex<UserInfo>>,
+        read_failures: Arc<AtomicU32>,
         slippi_semver: &str,
     ) {
         // If we're already watching, no-op out.
@@ -50,16 +53,50 @@ impl UserInfoWatcher {
 
         let watcher_thread = thread::Builder::new()
             .name("SlippiUserJSONWatcherThread".into())
-            .spawn(move || loop {
-                if !should_watch.load(Ordering::Relaxed) {
-                    return;
-                }
+            .spawn(move || {
+                // Held for the lifetime of the thread, as dropping it stops the notifications.
+                let (_watcher, events) = match watch_user_json_folder(&user_json_path) {
+                    Some((watcher, events)) => (Some(watcher), Some(events)),
+                    None => (None, None),
+                };
+
+                // With notifications we only need the occasional poll as a fallback (e.g, for
+                // network drives, where they may never fire). Without them, we poll often.
+                let poll_interval = match events {
+                    Some(_) => FALLBACK_POLL_INTERVAL,
+                    None => POLL_INTERVAL,
+                };
+
+                let mut last_attempt = Instant::now();
 
-                if attempt_login(&api_client, &user, &user_json_path, &slippi_semver) {
+                if attempt_login(&api_client, &user, &user_json_path, &read_failures, &slippi_semver) {
                     return;
                 }
 
-                thread::sleep(Duration::from_millis(500));
+                loop {
+                    let changed = match &events {
+                        Some(events) => wait_for_change(events, &user_json_path),
+
+                        None => {
+                            thread::sleep(POLL_INTERVAL);
+                            false
+                        },
+                    };
+
+                    if !should_watch.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    if !changed && last_attempt.elapsed() < poll_interval {
+                        continue;
+                    }
+
+                    last_attempt = Instant::now();
+
+                    if attempt_login(&api_client, &user, &user_json_path, &read_failures, &slippi_semver) {
+                        return;
+                    }
+                }
             })
             .expect("Failed to spawn SlippiUserJSONWatcherThread");
 
@@ -83,6 +120,62 @@ impl UserInfoWatcher {
     }
 }
 
+/// How often we check `user.json` when filesystem notifications are unavailable. This also
+/// bounds how long a watcher thread takes to notice it's been told to stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often we check `user.json` when filesystem notifications are working, in case they
+/// silently don't fire for whatever filesystem the file lives on.
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Starts watching the folder that `user.json` lives in (the file itself may not exist yet),
+/// returning the watcher and a channel of events. Returns `None`, after logging why, if
+/// notifications can't be set up - the caller should fall back to polling.
+fn watch_user_json_folder(user_json_path: &Path) -> Option<(RecommendedWatcher, Receiver<notify::Result<Event>>)> {
+    let folder = user_json_path.parent()?;
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher = match notify::recommended_watcher(sender) {
+        Ok(watcher) => watcher,
+
+        Err(error) => {
+            tracing::warn!(?error, "Unable to create user.json watcher, falling back to polling");
+            return None;
+        },
+    };
+
+    if let Err(error) = watcher.watch(folder, RecursiveMode::NonRecursive) {
+        tracing::warn!(?error, ?folder, "Unable to watch user.json folder, falling back to polling");
+        return None;
+    }
+
+    Some((watcher, receiver))
+}
+
+/// Waits up to `POLL_INTERVAL` for a filesystem event, returning whether `user.json` was
+/// created or modified.
+fn wait_for_change(events: &Receiver<notify::Result<Event>>, user_json_path: &Path) -> bool {
+    match events.recv_timeout(POLL_INTERVAL) {
+        Ok(Ok(event)) => {
+            matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                && event.paths.iter().any(|path| path.file_name() == user_json_path.file_name())
+        },
+
+        Ok(Err(error)) => {
+            tracing::warn!(?error, "Error watching user.json");
+            false
+        },
+
+        Err(RecvTimeoutError::Timeout) => false,
+
+        // Shouldn't happen while the watcher is alive, but don't spin if it does.
+        Err(RecvTimeoutError::Disconnected) => {
+            thread::sleep(POLL_INTERVAL);
+            false
+        },
+    }
+}
+
 impl Drop for UserInfoWatcher {
     /// Cleans up the background thread that we use for watching `user.json` status.
     fn drop(&mut self) {